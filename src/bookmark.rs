@@ -0,0 +1,261 @@
+use crate::{camera, camera_path::Keyframe, text_file};
+use anyhow::{bail, Context, Result};
+use egui_winit::winit::keyboard::KeyCode;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where bookmarks are kept, relative to the working directory.
+pub const BOOKMARKS_FILE: &str = "bookmarks.txt";
+
+/// Number of bookmarks reachable from the digit keys.
+pub const SLOTS: usize = 9;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub name: String,
+    /// Digit key slot, see `slot`.
+    pub slot: Option<usize>,
+    pub pose: Keyframe,
+}
+
+/// The pose as formatted by `Keyframe`, then the slot's digit key or `-` and
+/// the name, which may contain spaces.
+impl std::fmt::Display for Bookmark {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.slot {
+            Some(slot) => write!(f, "{} {} {}", self.pose, slot + 1, self.name),
+            None => write!(f, "{} - {}", self.pose, self.name),
+        }
+    }
+}
+
+impl std::str::FromStr for Bookmark {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // The name is whatever follows the six numbers of the pose and the slot
+        let mut fields = s.trim().splitn(8, ' ');
+        let pose = fields.by_ref().take(6).collect::<Vec<_>>().join(" ");
+        let slot = match fields.next() {
+            Some("-") => None,
+            Some(digit) => match digit.parse::<usize>() {
+                Ok(key @ 1..=SLOTS) => Some(key - 1),
+                _ => bail!("Invalid slot {}", digit),
+            },
+            None => bail!("Missing slot"),
+        };
+        Ok(Bookmark {
+            name: fields.next().unwrap_or_default().trim().to_string(),
+            slot,
+            pose: pose.parse()?,
+        })
+    }
+}
+
+/// Move from wherever the camera was to a bookmark.
+#[derive(Debug)]
+struct Transition {
+    from: Keyframe,
+    to: Keyframe,
+    elapsed: Duration,
+}
+
+/// Named camera poses, saved to their file on every change. Each line of the
+/// file is a bookmark as formatted by its `Display` implementation.
+#[derive(Debug)]
+pub struct Bookmarks {
+    pub bookmarks: Vec<Bookmark>,
+    /// Time taken to move to a bookmark, zero jumps straight there.
+    pub transition_duration: Duration,
+    path: PathBuf,
+    transition: Option<Transition>,
+}
+
+impl Bookmarks {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            bookmarks: Vec::new(),
+            transition_duration: Duration::from_millis(500),
+            path: path.into(),
+            transition: None,
+        }
+    }
+
+    /// Reads the bookmarks at `path`. A missing file is an empty list, which
+    /// is created with the first bookmark.
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let mut bookmarks = Self::new(path);
+        if bookmarks.path.exists() {
+            for (number, line) in text_file::read_lines(&bookmarks.path, "bookmarks")? {
+                bookmarks.bookmarks.push(
+                    line.parse()
+                        .with_context(|| format!("Invalid bookmark on line {}", number))?,
+                );
+            }
+        }
+        Ok(bookmarks)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut text = String::from("# x y z yaw pitch fovy key name\n");
+        for bookmark in &self.bookmarks {
+            text += &format!("{}\n", bookmark);
+        }
+        std::fs::write(&self.path, text)
+            .with_context(|| format!("Failed to write bookmarks {}", self.path.display()))
+    }
+
+    /// Bookmarks the current view as `name` and saves. With a `slot` the
+    /// bookmark in that slot is replaced, without one the bookmark takes the
+    /// first free slot if there is any.
+    pub fn add(
+        &mut self,
+        name: String,
+        slot: Option<usize>,
+        camera: &camera::Camera,
+        projection: &camera::Perspective,
+    ) -> Result<()> {
+        let slot = slot.or_else(|| (0..SLOTS).find(|slot| self.find_slot(*slot).is_none()));
+        let bookmark = Bookmark {
+            name,
+            slot,
+            pose: Keyframe::capture(camera, projection),
+        };
+        match slot.and_then(|slot| self.find_slot(slot)) {
+            Some(index) => self.bookmarks[index] = bookmark,
+            None => self.bookmarks.push(bookmark),
+        }
+        self.save()
+    }
+
+    /// Index of the bookmark in `slot`, if there is one.
+    pub fn find_slot(&self, slot: usize) -> Option<usize> {
+        self.bookmarks
+            .iter()
+            .position(|bookmark| bookmark.slot == Some(slot))
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<()> {
+        self.bookmarks.remove(index);
+        self.save()
+    }
+
+    /// Starts moving the camera to the bookmark at `index`, if there is one.
+    pub fn go_to(
+        &mut self,
+        index: usize,
+        camera: &camera::Camera,
+        projection: &camera::Perspective,
+    ) -> bool {
+        let Some(bookmark) = self.bookmarks.get(index) else {
+            return false;
+        };
+        let from = Keyframe::capture(camera, projection);
+        let mut to = bookmark.pose;
+        to.unwrap_yaw(&from);
+        self.transition = Some(Transition {
+            from,
+            to,
+            elapsed: Duration::ZERO,
+        });
+        true
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    /// Advances the move to a bookmark by `dt`, easing in and out. Returns
+    /// whether it is still under way.
+    pub fn update(
+        &mut self,
+        dt: Duration,
+        camera: &mut camera::Camera,
+        projection: &mut camera::Perspective,
+    ) -> bool {
+        let Some(transition) = &mut self.transition else {
+            return false;
+        };
+        transition.elapsed += dt;
+        let t = if transition.elapsed >= self.transition_duration {
+            1.0
+        } else {
+            transition.elapsed.as_secs_f32() / self.transition_duration.as_secs_f32()
+        };
+        let t = t * t * (3.0 - 2.0 * t);
+        let (from, to) = (&transition.from, &transition.to);
+        Keyframe {
+            position: from.position + (to.position - from.position) * t,
+            yaw: from.yaw + (to.yaw - from.yaw) * t,
+            pitch: from.pitch + (to.pitch - from.pitch) * t,
+            fovy: from.fovy + (to.fovy - from.fovy) * t,
+        }
+        .apply(camera, projection);
+
+        if t >= 1.0 {
+            self.transition = None;
+        }
+        self.transition.is_some()
+    }
+}
+
+/// Bookmark slot of the digit keys 1 to 9, which jump to the bookmark and
+/// store the current view with Ctrl held.
+pub fn slot(key: KeyCode) -> Option<usize> {
+    [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ]
+    .iter()
+    .position(|&digit| digit == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Point3, Rad};
+
+    fn bookmark(name: &str, slot: Option<usize>) -> Bookmark {
+        Bookmark {
+            name: name.to_string(),
+            slot,
+            pose: Keyframe {
+                position: Point3::new(1.5, -2.0, 30.25),
+                yaw: Rad(-1.25),
+                pitch: Rad(0.5),
+                fovy: Rad(0.75),
+            },
+        }
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        for bookmark in [
+            bookmark("Tower top", Some(4)),
+            bookmark("No slot", None),
+            bookmark("", Some(0)),
+            bookmark("7 dwarves", None),
+        ] {
+            assert_eq!(bookmark.to_string().parse::<Bookmark>().unwrap(), bookmark);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        assert!("1 2 3 4 5".parse::<Bookmark>().is_err());
+        assert!("1 2 3 4 5 6".parse::<Bookmark>().is_err());
+        assert!("1 2 3 4 5 6 0 Zero".parse::<Bookmark>().is_err());
+        assert!("1 2 3 4 5 x - Name".parse::<Bookmark>().is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use cgmath::prelude::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: cgmath::Point3<f32>,
+    pub max: cgmath::Point3<f32>,
+}
+
+impl Aabb {
+    pub fn from_points(points: impl IntoIterator<Item = cgmath::Point3<f32>>) -> Self {
+        let mut points = points.into_iter();
+        let first = points.next().unwrap_or_else(cgmath::Point3::origin);
+        points.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |aabb, p| Self {
+                min: cgmath::Point3::new(
+                    aabb.min.x.min(p.x),
+                    aabb.min.y.min(p.y),
+                    aabb.min.z.min(p.z),
+                ),
+                max: cgmath::Point3::new(
+                    aabb.max.x.max(p.x),
+                    aabb.max.y.max(p.y),
+                    aabb.max.z.max(p.z),
+                ),
+            },
+        )
+    }
+
+    pub fn center(&self) -> cgmath::Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    pub fn half_extents(&self) -> cgmath::Vector3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    /// The box enclosing this one after it has been transformed by `matrix`.
+    pub fn transform(&self, matrix: &cgmath::Matrix4<f32>) -> Self {
+        let center = matrix.transform_point(self.center());
+        let extents = self.half_extents();
+        let abs = |v: cgmath::Vector4<f32>| cgmath::Vector3::new(v.x.abs(), v.y.abs(), v.z.abs());
+        let half =
+            abs(matrix.x) * extents.x + abs(matrix.y) * extents.y + abs(matrix.z) * extents.z;
+        Self {
+            min: center - half,
+            max: center + half,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingSphere {
+    pub center: cgmath::Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Sphere around the center of `aabb` reaching the furthest point. Not the
+    /// tightest possible fit, but cheap and good enough for culling.
+    pub fn from_points(aabb: &Aabb, points: impl IntoIterator<Item = cgmath::Point3<f32>>) -> Self {
+        let center = aabb.center();
+        let radius = points
+            .into_iter()
+            .map(|p| p.distance(center))
+            .fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    pub fn transform(&self, matrix: &cgmath::Matrix4<f32>) -> Self {
+        let scale = matrix
+            .x
+            .truncate()
+            .magnitude()
+            .max(matrix.y.truncate().magnitude())
+            .max(matrix.z.truncate().magnitude());
+        Self {
+            center: matrix.transform_point(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// A half-line from `origin`, such as the one under the cursor.
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: cgmath::Point3<f32>,
+    /// Normalized, so that distances along the ray are in world units.
+    pub direction: cgmath::Vector3<f32>,
+}
+
+impl Ray {
+    /// The same ray in the space `matrix` maps to. Distances along it only
+    /// carry over if `matrix` doesn't scale.
+    pub fn transform(&self, matrix: &cgmath::Matrix4<f32>) -> Self {
+        Self {
+            origin: matrix.transform_point(self.origin),
+            direction: matrix.transform_vector(self.direction),
+        }
+    }
+
+    /// Distance to where the ray enters `aabb`, or zero if it starts inside.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            // Dividing by zero gives infinities which the comparisons handle
+            let inverse = 1.0 / self.direction[axis];
+            let t0 = (aabb.min[axis] - self.origin[axis]) * inverse;
+            let t1 = (aabb.max[axis] - self.origin[axis]) * inverse;
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        (near <= far).then_some(near)
+    }
+
+    /// Distance to where the ray enters `sphere`, or zero if it starts
+    /// inside.
+    pub fn intersect_sphere(&self, sphere: &BoundingSphere) -> Option<f32> {
+        let offset = self.origin - sphere.center;
+        let b = offset.dot(self.direction);
+        let c = offset.magnitude2() - sphere.radius * sphere.radius;
+        let discriminant = b * b - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let far = -b + discriminant.sqrt();
+        (far >= 0.0).then(|| (-b - discriminant.sqrt()).max(0.0))
+    }
+}
@@ -1,22 +1,33 @@
+use crate::{bounds, input};
 use cgmath::*;
 use egui_winit::winit::{
     dpi::PhysicalPosition,
-    event::{ElementState, MouseScrollDelta},
-    keyboard::KeyCode,
+    event::{ElementState, MouseButton, MouseScrollDelta},
 };
 use std::f32::consts::FRAC_PI_2;
 use std::time::Duration;
 
+/// Maps OpenGL's -1..1 clip space depth to wgpu's 0..1, as z' = 0.5 z + 0.5 w.
+/// Written column by column, so the 0.5 w lands in the last one. Putting it
+/// in the third column instead gives w' = w + 0.5 z, which also divides x
+/// and y by that and shrinks the whole image.
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
 );
 
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 
+/// Turn of the mouse in radians per pixel moved, before sensitivity.
+const MOUSE_TURN_PER_PIXEL: f32 = 0.005;
+
+/// Turn rate of a fully deflected stick in radians per second, before
+/// sensitivity.
+const STICK_TURN_RATE: f32 = 2.0;
+
 #[derive(Debug)]
 pub struct Camera {
     pub position: Point3<f32>,
@@ -49,32 +60,380 @@ impl Camera {
     }
 }
 
-pub struct Projection {
+/// Flips wgpu's 0..1 clip space depth, putting the near plane at 1.
+#[rustfmt::skip]
+const REVERSE_Z_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, -1.0, 0.0,
+    0.0, 0.0, 1.0, 1.0,
+);
+
+/// How distances from the camera map to the depth buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthMode {
+    /// The near plane at 0 and the far plane at 1.
+    Standard,
+    /// The near plane at 1 and the far plane at 0, pushed out to infinity by
+    /// perspective projections. Float precision grows towards 0, which
+    /// evens out the precision lost to the perspective divide.
+    ReverseZ,
+}
+
+impl DepthMode {
+    pub const ALL: [DepthMode; 2] = [DepthMode::Standard, DepthMode::ReverseZ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DepthMode::Standard => "Standard",
+            DepthMode::ReverseZ => "Reverse-Z",
+        }
+    }
+
+    /// Depth test letting nearer surfaces through.
+    pub fn compare(&self) -> egui_wgpu::wgpu::CompareFunction {
+        match self {
+            DepthMode::Standard => egui_wgpu::wgpu::CompareFunction::Less,
+            DepthMode::ReverseZ => egui_wgpu::wgpu::CompareFunction::Greater,
+        }
+    }
+
+    /// Depth of the near plane.
+    pub fn near_depth(&self) -> f32 {
+        match self {
+            DepthMode::Standard => 0.0,
+            DepthMode::ReverseZ => 1.0,
+        }
+    }
+
+    /// Depth of the far plane, which depth buffers are cleared to.
+    pub fn far_depth(&self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::ReverseZ => 0.0,
+        }
+    }
+}
+
+/// Maps view space to wgpu's clip space.
+pub trait Projection {
+    fn calc_matrix(&self) -> Matrix4<f32>;
+    fn resize(&mut self, width: u32, height: u32);
+    fn znear(&self) -> f32;
+    fn zfar(&self) -> f32;
+    fn depth_mode(&self) -> DepthMode;
+    /// Distance in front of the camera at which the view spans `half_height`
+    /// above and below its center. Infinite if it never gets that tall, zero
+    /// if it always is.
+    fn distance_for_half_height(&self, half_height: f32) -> f32;
+}
+
+pub struct Perspective {
     pub aspect: f32,
     pub fovy: Rad<f32>,
     pub znear: f32,
+    /// Ignored with `DepthMode::ReverseZ`, which has no far plane.
     pub zfar: f32,
+    pub depth_mode: DepthMode,
 }
 
-impl Projection {
+impl Perspective {
     pub fn new<F: Into<Rad<f32>>>(width: u32, height: u32, fovy: F, znear: f32, zfar: f32) -> Self {
         Self {
             aspect: width as f32 / height as f32,
             fovy: fovy.into(),
             znear,
             zfar,
+            depth_mode: DepthMode::Standard,
         }
     }
+}
 
-    pub fn resize(&mut self, width: u32, height: u32) {
+impl Projection for Perspective {
+    fn calc_matrix(&self) -> Matrix4<f32> {
+        match self.depth_mode {
+            DepthMode::Standard => {
+                OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
+            }
+            // The limit of the reversed projection as zfar goes to infinity,
+            // leaving depth at znear / distance
+            DepthMode::ReverseZ => {
+                let f = 1.0 / (self.fovy.0 * 0.5).tan();
+                #[rustfmt::skip]
+                let matrix = Matrix4::new(
+                    f / self.aspect, 0.0, 0.0, 0.0,
+                    0.0, f, 0.0, 0.0,
+                    0.0, 0.0, 0.0, -1.0,
+                    0.0, 0.0, self.znear, 0.0,
+                );
+                matrix
+            }
+        }
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
         self.aspect = width as f32 / height as f32;
     }
 
-    pub fn calc_matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
+    fn znear(&self) -> f32 {
+        self.znear
+    }
+
+    fn zfar(&self) -> f32 {
+        self.zfar
+    }
+
+    fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    fn distance_for_half_height(&self, half_height: f32) -> f32 {
+        half_height / (self.fovy.0 * 0.5).tan()
+    }
+}
+
+pub struct Orthographic {
+    pub aspect: f32,
+    /// Height of the view in world units, smaller values zoom in.
+    pub height: f32,
+    pub znear: f32,
+    pub zfar: f32,
+    pub depth_mode: DepthMode,
+}
+
+impl Orthographic {
+    pub fn new(width: u32, height: u32, view_height: f32, znear: f32, zfar: f32) -> Self {
+        Self {
+            aspect: width as f32 / height as f32,
+            height: view_height,
+            znear,
+            zfar,
+            depth_mode: DepthMode::Standard,
+        }
+    }
+
+    /// Zooms by changing the view height, as moving closer doesn't.
+    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
+        let scroll = match delta {
+            MouseScrollDelta::LineDelta(_, scroll) => -scroll,
+            MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => {
+                -*scroll as f32 / 100.0
+            }
+        };
+        self.height = (self.height * 1.1f32.powf(scroll)).max(0.01);
+    }
+}
+
+impl Projection for Orthographic {
+    fn calc_matrix(&self) -> Matrix4<f32> {
+        let half_height = self.height * 0.5;
+        let half_width = half_height * self.aspect;
+        let matrix = OPENGL_TO_WGPU_MATRIX
+            * ortho(
+                -half_width,
+                half_width,
+                -half_height,
+                half_height,
+                self.znear,
+                self.zfar,
+            );
+        match self.depth_mode {
+            DepthMode::Standard => matrix,
+            DepthMode::ReverseZ => REVERSE_Z_MATRIX * matrix,
+        }
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.aspect = width as f32 / height as f32;
+    }
+
+    fn znear(&self) -> f32 {
+        self.znear
+    }
+
+    fn zfar(&self) -> f32 {
+        self.zfar
+    }
+
+    fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    fn distance_for_half_height(&self, half_height: f32) -> f32 {
+        if half_height <= self.height * 0.5 {
+            0.0
+        } else {
+            f32::INFINITY
+        }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectionKind {
+    Perspective,
+    Orthographic,
+}
+
+/// Both projections, of which the one picked by `kind` is in use. Each keeps
+/// its settings while the other one is active.
+pub struct Projections {
+    pub kind: ProjectionKind,
+    pub perspective: Perspective,
+    pub orthographic: Orthographic,
+}
+
+impl Projections {
+    pub fn active(&self) -> &dyn Projection {
+        match self.kind {
+            ProjectionKind::Perspective => &self.perspective,
+            ProjectionKind::Orthographic => &self.orthographic,
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.perspective.resize(width, height);
+        self.orthographic.resize(width, height);
+    }
+
+    /// Shared by both, as the depth buffer and pipelines are.
+    pub fn depth_mode(&self) -> DepthMode {
+        self.perspective.depth_mode
+    }
+
+    pub fn set_depth_mode(&mut self, depth_mode: DepthMode) {
+        self.perspective.depth_mode = depth_mode;
+        self.orthographic.depth_mode = depth_mode;
+    }
+}
+
+/// Axis aligned views, bound to the numpad like in modelling tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisView {
+    /// Looking along -Z.
+    Front,
+    Back,
+    /// Looking along -X.
+    Right,
+    Left,
+    /// Looking down along -Y, with -Z up on screen.
+    Top,
+    Bottom,
+}
+
+impl AxisView {
+    /// The view of one of the view actions, or of the opposite one.
+    pub fn from_action(action: input::Action, opposite: bool) -> Option<Self> {
+        match (action, opposite) {
+            (input::Action::FrontView, false) => Some(AxisView::Front),
+            (input::Action::FrontView, true) => Some(AxisView::Back),
+            (input::Action::RightView, false) => Some(AxisView::Right),
+            (input::Action::RightView, true) => Some(AxisView::Left),
+            (input::Action::TopView, false) => Some(AxisView::Top),
+            (input::Action::TopView, true) => Some(AxisView::Bottom),
+            _ => None,
+        }
+    }
+
+    /// Yaw and pitch of a `Camera` looking this way. Top and bottom stop
+    /// just short of straight down and up, like the controllers do.
+    pub fn yaw_pitch(&self) -> (Rad<f32>, Rad<f32>) {
+        match self {
+            AxisView::Front => (Rad(-FRAC_PI_2), Rad(0.0)),
+            AxisView::Back => (Rad(FRAC_PI_2), Rad(0.0)),
+            AxisView::Right => (Rad(std::f32::consts::PI), Rad(0.0)),
+            AxisView::Left => (Rad(0.0), Rad(0.0)),
+            AxisView::Top => (Rad(-FRAC_PI_2), Rad(-SAFE_FRAC_PI_2)),
+            AxisView::Bottom => (Rad(-FRAC_PI_2), Rad(SAFE_FRAC_PI_2)),
+        }
+    }
+}
+
+/// Ray from the near plane through `cursor`, in pixels from the top left of
+/// a `width` by `height` view.
+pub fn cursor_ray(
+    camera: &Camera,
+    projection: &dyn Projection,
+    cursor: (f32, f32),
+    width: u32,
+    height: u32,
+) -> bounds::Ray {
+    let x = 2.0 * cursor.0 / width as f32 - 1.0;
+    let y = 1.0 - 2.0 * cursor.1 / height as f32;
+    let inverse = (projection.calc_matrix() * camera.calc_matrix())
+        .invert()
+        .unwrap_or(Matrix4::identity());
+    let unproject = |depth| Point3::from_homogeneous(inverse * Vector4::new(x, y, depth, 1.0));
+    // Halfway through the depth range is still finite with an infinite far
+    // plane, and the same side of the near plane either way
+    let near = unproject(projection.depth_mode().near_depth());
+    let further = unproject(0.5);
+    bounds::Ray {
+        origin: near,
+        direction: (further - near).normalize(),
+    }
+}
+
+/// The six clipping planes of a view-projection matrix, each stored as
+/// `(normal, distance)` with the normal pointing into the frustum.
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    pub planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    pub fn from_matrix(view_proj: Matrix4<f32>) -> Self {
+        let row = |i| view_proj.row(i);
+        // wgpu clip space has z in [0, 1], so the near plane is just the z row
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2),
+        ]
+        .map(|plane| {
+            let length = plane.truncate().magnitude();
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        });
+
+        Self { planes }
+    }
+
+    pub fn contains_sphere(&self, sphere: &bounds::BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(sphere.center.to_vec()) + plane.w >= -sphere.radius)
+    }
+
+    pub fn intersects_aabb(&self, aabb: &bounds::Aabb) -> bool {
+        let center = aabb.center().to_vec();
+        let extents = aabb.half_extents();
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            let reach = extents.x * normal.x.abs()
+                + extents.y * normal.y.abs()
+                + extents.z * normal.z.abs();
+            normal.dot(center) + plane.w >= -reach
+        })
+    }
+}
+
+/// Which controller drives the camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerKind {
+    /// First person fly camera, see `CameraController`.
+    Fly,
+    /// Rotates around a target, see `OrbitController`.
+    Orbit,
+}
+
+/// First person fly camera. Without smoothing and inertia it follows the
+/// input directly, both can be turned on through the public fields.
 #[derive(Debug)]
 pub struct CameraController {
     amount_left: f32,
@@ -86,8 +445,28 @@ pub struct CameraController {
     rotate_horizontal: f32,
     rotate_vertical: f32,
     scroll: f32,
-    speed: f32,
-    sensitivity: f32,
+    sprinting: bool,
+    adjusting_speed: bool,
+    stick_movement: Vector3<f32>,
+    stick_look: Vector2<f32>,
+    stick_sprinting: bool,
+    velocity: Vector3<f32>,
+    /// Movement speed in units per second.
+    pub speed: f32,
+    pub sensitivity: f32,
+    /// Speed factor while `Action::Sprint` is held.
+    pub sprint_multiplier: f32,
+    /// Time constant of the rotation smoothing in seconds, after which about
+    /// two thirds of a mouse movement has been applied. Zero turns it off.
+    pub smoothing: f32,
+    /// Keeps a velocity which builds up and dies down over time, rather than
+    /// moving at full speed for exactly as long as a key is held.
+    pub inertia: bool,
+    /// How fast the velocity approaches the input, in units per second
+    /// squared.
+    pub acceleration: f32,
+    /// Rate at which the velocity decays without input, per second.
+    pub damping: f32,
 }
 
 impl CameraController {
@@ -102,68 +481,126 @@ impl CameraController {
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
             scroll: 0.0,
+            sprinting: false,
+            adjusting_speed: false,
+            stick_movement: Vector3::zero(),
+            stick_look: Vector2::zero(),
+            stick_sprinting: false,
+            velocity: Vector3::zero(),
             speed,
             sensitivity,
+            sprint_multiplier: 3.0,
+            smoothing: 0.0,
+            inertia: false,
+            acceleration: 40.0,
+            damping: 8.0,
         }
     }
 
-    pub fn process_keyboard(&mut self, key: KeyCode, state: ElementState) -> bool {
-        let amount = if state == ElementState::Pressed {
-            1.0
-        } else {
-            0.0
-        };
-        match key {
-            KeyCode::KeyW | KeyCode::ArrowUp => {
+    pub fn process_action(&mut self, action: input::Action, state: ElementState) -> bool {
+        let pressed = state == ElementState::Pressed;
+        let amount = if pressed { 1.0 } else { 0.0 };
+        match action {
+            input::Action::MoveForward => {
                 self.amount_forward = amount;
                 true
             }
-            KeyCode::KeyA | KeyCode::ArrowLeft => {
+            input::Action::MoveLeft => {
                 self.amount_left = amount;
                 true
             }
-            KeyCode::KeyS | KeyCode::ArrowDown => {
+            input::Action::MoveBackward => {
                 self.amount_backward = amount;
                 true
             }
-            KeyCode::KeyD | KeyCode::ArrowRight => {
+            input::Action::MoveRight => {
                 self.amount_right = amount;
                 true
             }
-            KeyCode::Space => {
+            input::Action::MoveUp => {
                 self.amount_up = amount;
                 true
             }
-            KeyCode::ShiftLeft => {
+            input::Action::MoveDown => {
                 self.amount_down = amount;
                 true
             }
+            input::Action::Sprint => {
+                self.sprinting = pressed;
+                true
+            }
+            // Scrolling changes the speed while held
+            input::Action::AdjustSpeed => {
+                self.adjusting_speed = pressed;
+                true
+            }
             _ => false,
         }
     }
 
+    /// Mouse movement is accumulated until `update_camera` applies it.
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx as f32;
-        self.rotate_vertical = mouse_dy as f32;
+        self.rotate_horizontal += mouse_dx as f32;
+        self.rotate_vertical += mouse_dy as f32;
+    }
+
+    /// Analog input such as a gamepad's, held until the next call.
+    /// `movement` is right, up and forward and `look` turns right and up,
+    /// each from -1 to 1.
+    pub fn process_stick(&mut self, movement: Vector3<f32>, look: Vector2<f32>, sprinting: bool) {
+        self.stick_movement = movement;
+        self.stick_look = look;
+        self.stick_sprinting = sprinting;
     }
 
     pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
-        self.scroll = match delta {
+        let scroll = match delta {
             // I'm assuming a line is about 100 pixels
             MouseScrollDelta::LineDelta(_, scroll) => -scroll * 0.5,
             MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => -*scroll as f32,
         };
+        if self.adjusting_speed {
+            // Exponential, so that every step feels the same at any speed
+            self.speed = (self.speed * 1.2f32.powf(-scroll)).clamp(0.1, 1000.0);
+        } else {
+            self.scroll = scroll;
+        }
     }
 
     pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
         let dt = dt.as_secs_f32();
 
-        // Move forward/backward and left/right
+        // Move forward/backward, left/right and up/down. Since we don't use
+        // roll, up is just the y axis.
         let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
         let forward = Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
         let right = Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
-        camera.position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
+        let speed = if self.sprinting || self.stick_sprinting {
+            self.speed * self.sprint_multiplier
+        } else {
+            self.speed
+        };
+        let input = forward * (self.amount_forward - self.amount_backward)
+            + right * (self.amount_right - self.amount_left)
+            + Vector3::unit_y() * (self.amount_up - self.amount_down)
+            + right * self.stick_movement.x
+            + Vector3::unit_y() * self.stick_movement.y
+            + forward * self.stick_movement.z;
+        let target_velocity = input * speed;
+        if !self.inertia {
+            self.velocity = target_velocity;
+        } else if input == Vector3::zero() {
+            self.velocity *= (-self.damping * dt).exp();
+        } else {
+            let change = target_velocity - self.velocity;
+            let max_change = self.acceleration * dt;
+            self.velocity += if change.magnitude() > max_change {
+                change.normalize_to(max_change)
+            } else {
+                change
+            };
+        }
+        camera.position += self.velocity * dt;
 
         // Move in/out (aka. "zoom")
         // Note: this isn't an actual zoom. The camera's position
@@ -175,19 +612,23 @@ impl CameraController {
         camera.position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
         self.scroll = 0.0;
 
-        // Move up/down. Since we don't use roll, we can just
-        // modify the y coordinate directly.
-        camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
-
-        // Rotate
-        camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
-        camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
+        // Rotate by the part of the pending mouse movement the smoothing lets
+        // through this frame, keeping the rest for the next ones
+        let applied = if self.smoothing > 0.0 {
+            1.0 - (-dt / self.smoothing).exp()
+        } else {
+            1.0
+        };
+        let angle = MOUSE_TURN_PER_PIXEL * self.sensitivity;
+        camera.yaw += Rad(self.rotate_horizontal * applied * angle);
+        camera.pitch += Rad(-self.rotate_vertical * applied * angle);
+        self.rotate_horizontal *= 1.0 - applied;
+        self.rotate_vertical *= 1.0 - applied;
 
-        // If process_mouse isn't called every frame, these values
-        // will not get set to zero, and the camera will rotate
-        // when moving in a non cardinal direction.
-        self.rotate_horizontal = 0.0;
-        self.rotate_vertical = 0.0;
+        // Sticks give a turn rate rather than a distance
+        let turn = STICK_TURN_RATE * self.sensitivity * dt;
+        camera.yaw += Rad(self.stick_look.x * turn);
+        camera.pitch += Rad(self.stick_look.y * turn);
 
         // Keep the camera's angle from going too high/low.
         if camera.pitch < -Rad(SAFE_FRAC_PI_2) {
@@ -197,3 +638,183 @@ impl CameraController {
         }
     }
 }
+
+/// Keeps the camera looking at `target` from `distance` away. Dragging with
+/// the left mouse button rotates around the target, the middle button pans
+/// it and scrolling zooms in and out. Sticks do the same through
+/// `process_stick`.
+#[derive(Debug)]
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    yaw: Rad<f32>,
+    pitch: Rad<f32>,
+    rotating: bool,
+    panning: bool,
+    rotate_horizontal: f32,
+    rotate_vertical: f32,
+    pan_horizontal: f32,
+    pan_vertical: f32,
+    scroll: f32,
+    stick_rotate: Vector2<f32>,
+    stick_pan: Vector2<f32>,
+    stick_zoom: f32,
+    sensitivity: f32,
+}
+
+impl OrbitController {
+    pub fn new<P: Into<Point3<f32>>>(target: P, distance: f32, sensitivity: f32) -> Self {
+        Self {
+            target: target.into(),
+            distance,
+            yaw: Rad(-FRAC_PI_2),
+            pitch: Rad(0.0),
+            rotating: false,
+            panning: false,
+            rotate_horizontal: 0.0,
+            rotate_vertical: 0.0,
+            pan_horizontal: 0.0,
+            pan_vertical: 0.0,
+            scroll: 0.0,
+            stick_rotate: Vector2::zero(),
+            stick_pan: Vector2::zero(),
+            stick_zoom: 0.0,
+            sensitivity,
+        }
+    }
+
+    /// Turns to look at the target from `yaw` and `pitch`.
+    pub fn set_angles(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        self.yaw = yaw;
+        self.pitch = pitch;
+    }
+
+    /// Takes over from wherever `camera` is, orbiting the point `distance`
+    /// in front of it so that the view doesn't jump.
+    pub fn look_from(&mut self, camera: &Camera) {
+        self.yaw = camera.yaw;
+        self.pitch = camera.pitch;
+        self.target = camera.position + self.forward() * self.distance;
+    }
+
+    pub fn process_button(&mut self, button: MouseButton, state: ElementState) -> bool {
+        let pressed = state == ElementState::Pressed;
+        match button {
+            MouseButton::Left => {
+                self.rotating = pressed;
+                true
+            }
+            MouseButton::Middle => {
+                self.panning = pressed;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        // Accumulated, as several motion events can arrive between frames
+        if self.rotating {
+            self.rotate_horizontal += mouse_dx as f32;
+            self.rotate_vertical += mouse_dy as f32;
+        }
+        if self.panning {
+            self.pan_horizontal += mouse_dx as f32;
+            self.pan_vertical += mouse_dy as f32;
+        }
+    }
+
+    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
+        self.scroll += match delta {
+            MouseScrollDelta::LineDelta(_, scroll) => -scroll,
+            MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => {
+                -*scroll as f32 / 100.0
+            }
+        };
+    }
+
+    /// Analog input such as a gamepad's, held until the next call. `rotate`
+    /// turns right and up, `pan` moves the target right and forward along
+    /// the ground and `zoom` moves out, each from -1 to 1.
+    pub fn process_stick(&mut self, rotate: Vector2<f32>, pan: Vector2<f32>, zoom: f32) {
+        self.stick_rotate = rotate;
+        self.stick_pan = pan;
+        self.stick_zoom = zoom;
+    }
+
+    fn forward(&self) -> Vector3<f32> {
+        let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
+        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
+    }
+
+    /// Mouse movement is already a distance, so unlike `CameraController`
+    /// it doesn't scale with `dt`. Stick input is a rate and does.
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+        let dt = dt.as_secs_f32();
+
+        let angle = MOUSE_TURN_PER_PIXEL * self.sensitivity;
+        let turn = STICK_TURN_RATE * self.sensitivity * dt;
+        self.yaw += Rad(self.rotate_horizontal * angle + self.stick_rotate.x * turn);
+        self.pitch = Rad((self.pitch.0 - self.rotate_vertical * angle
+            + self.stick_rotate.y * turn)
+            .clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2));
+
+        // Pan by the same fraction of the view at any distance
+        let forward = self.forward();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        let pan = self.distance * 0.001 * self.sensitivity;
+        self.target += (up * self.pan_vertical - right * self.pan_horizontal) * pan;
+        // A full stick crosses the view's distance in a second
+        let ground_forward = Vector3::new(forward.x, 0.0, forward.z).normalize();
+        self.target +=
+            (right * self.stick_pan.x + ground_forward * self.stick_pan.y) * self.distance * dt;
+
+        // Zoom exponentially, so that every scroll step feels the same
+        self.distance =
+            (self.distance * 1.1f32.powf(self.scroll) * (self.stick_zoom * dt).exp()).max(0.1);
+
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+        self.pan_horizontal = 0.0;
+        self.pan_vertical = 0.0;
+        self.scroll = 0.0;
+
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch;
+        camera.position = self.target - forward * self.distance;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perspective_maps_depth_without_touching_w() {
+        let projection = Perspective::new(1, 1, Deg(90.0), 0.5, 100.0);
+        let matrix = projection.calc_matrix();
+        for (distance, depth) in [(0.5, 0.0), (100.0, 1.0)] {
+            let clip = matrix * Vector4::new(distance, distance, -distance, 1.0);
+            assert!(
+                (clip.w - distance).abs() < 1e-4,
+                "w {} at {}",
+                clip.w,
+                distance
+            );
+            assert!(
+                (clip.x / clip.w - 1.0).abs() < 1e-4,
+                "x {} at {}",
+                clip.x,
+                distance
+            );
+            assert!(
+                (clip.z / clip.w - depth).abs() < 1e-4,
+                "z {} at {}",
+                clip.z,
+                distance
+            );
+        }
+    }
+}
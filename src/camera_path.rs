@@ -0,0 +1,344 @@
+use crate::{camera, text_file};
+use anyhow::{bail, Context, Result};
+use cgmath::*;
+use std::f32::consts::PI;
+use std::time::Duration;
+
+/// A camera pose along a `CameraPath`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub position: Point3<f32>,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+    pub fovy: Rad<f32>,
+}
+
+impl Keyframe {
+    pub fn capture(camera: &camera::Camera, projection: &camera::Perspective) -> Self {
+        Self {
+            position: camera.position,
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            fovy: projection.fovy,
+        }
+    }
+
+    pub fn apply(&self, camera: &mut camera::Camera, projection: &mut camera::Perspective) {
+        camera.position = self.position;
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch;
+        projection.fovy = self.fovy;
+    }
+
+    /// Moves `yaw` by whole turns to within half a turn of `other`, so that
+    /// interpolating between the two takes the short way around.
+    pub fn unwrap_yaw(&mut self, other: &Keyframe) {
+        let turns = ((self.yaw.0 - other.yaw.0) / (2.0 * PI)).round();
+        self.yaw.0 -= turns * 2.0 * PI;
+    }
+}
+
+/// The position followed by yaw, pitch and field of view in radians,
+/// separated by spaces.
+impl std::fmt::Display for Keyframe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {}",
+            self.position.x,
+            self.position.y,
+            self.position.z,
+            self.yaw.0,
+            self.pitch.0,
+            self.fovy.0,
+        )
+    }
+}
+
+impl std::str::FromStr for Keyframe {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let values = s
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<f32>, _>>()
+            .context("Invalid number")?;
+        let [x, y, z, yaw, pitch, fovy] = values[..] else {
+            bail!("Expected 6 values, got {}", values.len());
+        };
+        Ok(Keyframe {
+            position: Point3::new(x, y, z),
+            yaw: Rad(yaw),
+            pitch: Rad(pitch),
+            fovy: Rad(fovy),
+        })
+    }
+}
+
+/// Recorded camera keyframes played back along a Catmull-Rom spline through
+/// all of them, each segment taking the same share of `duration`. Looping
+/// paths get a closing segment from the last keyframe back to the first.
+///
+/// Paths are saved as text, one keyframe per line as formatted by its
+/// `Display` implementation. Lines starting with `#` are ignored.
+#[derive(Debug)]
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+    /// Time from the first to the last keyframe.
+    pub duration: Duration,
+    /// Goes on from the last keyframe back to the first and starts over.
+    pub looping: bool,
+    /// Time into the path while playing.
+    playback: Option<Duration>,
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        Self {
+            keyframes: Vec::new(),
+            duration: Duration::from_secs(10),
+            looping: false,
+            playback: None,
+        }
+    }
+}
+
+impl CameraPath {
+    /// Appends the current view. Yaw is unwrapped towards the previous
+    /// keyframe, so the spline takes the short way around.
+    pub fn record(&mut self, camera: &camera::Camera, projection: &camera::Perspective) {
+        let mut keyframe = Keyframe::capture(camera, projection);
+        if let Some(previous) = self.keyframes.last() {
+            keyframe.unwrap_yaw(previous);
+        }
+        self.keyframes.push(keyframe);
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+        self.playback = None;
+    }
+
+    /// Starts from the first keyframe. Needs at least two of them.
+    pub fn play(&mut self) {
+        if self.keyframes.len() >= 2 {
+            self.playback = Some(Duration::ZERO);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.playback = None;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Fraction of the path played so far.
+    pub fn progress(&self) -> Option<f32> {
+        self.playback
+            .map(|elapsed| (elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0))
+    }
+
+    /// Advances playback by `dt` and moves the camera there. Returns whether
+    /// the path is still playing, playback stops at the last keyframe unless
+    /// `looping` is set.
+    pub fn update(
+        &mut self,
+        dt: Duration,
+        camera: &mut camera::Camera,
+        projection: &mut camera::Perspective,
+    ) -> bool {
+        let Some(elapsed) = self.playback else {
+            return false;
+        };
+        let mut elapsed = elapsed + dt;
+        if elapsed >= self.duration {
+            if self.looping && !self.duration.is_zero() {
+                elapsed =
+                    Duration::from_secs_f32(elapsed.as_secs_f32() % self.duration.as_secs_f32());
+            } else {
+                elapsed = self.duration;
+            }
+        }
+
+        if let Some(keyframe) = self.sample(elapsed.as_secs_f32() / self.duration.as_secs_f32()) {
+            keyframe.apply(camera, projection);
+        }
+        self.playback = (elapsed < self.duration).then_some(elapsed);
+        self.playback.is_some()
+    }
+
+    /// Pose at `t` between 0 for the first keyframe and 1 for the end of the
+    /// path, which is the last keyframe or, when looping, the first again.
+    pub fn sample(&self, t: f32) -> Option<Keyframe> {
+        let count = self.keyframes.len();
+        if count <= 1 {
+            return self.keyframes.first().copied();
+        }
+
+        let segments = if self.looping { count } else { count - 1 };
+        let position = t.clamp(0.0, 1.0) * segments as f32;
+        let segment = (position as usize).min(segments - 1);
+        let t = position - segment as f32;
+        let [mut p0, p1, mut p2, mut p3] = if self.looping {
+            [segment + count - 1, segment, segment + 1, segment + 2].map(|i| i % count)
+        } else {
+            // The end points are repeated to give the outer segments a tangent
+            [
+                segment.saturating_sub(1),
+                segment,
+                segment + 1,
+                (segment + 2).min(count - 1),
+            ]
+        }
+        .map(|i| self.keyframes[i]);
+        // Only needed around the closing segment, recorded and loaded
+        // keyframes are already unwrapped
+        p0.unwrap_yaw(&p1);
+        p2.unwrap_yaw(&p1);
+        p3.unwrap_yaw(&p2);
+
+        let position = catmull_rom(
+            p0.position.to_vec(),
+            p1.position.to_vec(),
+            p2.position.to_vec(),
+            p3.position.to_vec(),
+            t,
+        );
+        let angle = |f: fn(&Keyframe) -> Rad<f32>| {
+            Rad(catmull_rom(f(&p0).0, f(&p1).0, f(&p2).0, f(&p3).0, t))
+        };
+        Some(Keyframe {
+            position: Point3::from_vec(position),
+            yaw: angle(|keyframe| keyframe.yaw),
+            pitch: angle(|keyframe| keyframe.pitch),
+            fovy: angle(|keyframe| keyframe.fovy),
+        })
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<()> {
+        let mut text = String::from("# x y z yaw pitch fovy\n");
+        for keyframe in &self.keyframes {
+            text += &format!("{}\n", keyframe);
+        }
+        std::fs::write(path, text)
+            .with_context(|| format!("Failed to write camera path {}", path.display()))
+    }
+
+    /// Replaces the keyframes with the ones in the file at `path`, stopping
+    /// playback. Yaw is unwrapped as when recording.
+    pub fn load(&mut self, path: &std::path::Path) -> Result<()> {
+        let mut keyframes: Vec<Keyframe> = Vec::new();
+        for (number, line) in text_file::read_lines(path, "camera path")? {
+            keyframes.push(
+                line.parse()
+                    .with_context(|| format!("Invalid keyframe on line {}", number))?,
+            );
+        }
+        for i in 1..keyframes.len() {
+            let previous = keyframes[i - 1];
+            keyframes[i].unwrap_yaw(&previous);
+        }
+        self.keyframes = keyframes;
+        self.playback = None;
+        Ok(())
+    }
+}
+
+/// Uniform Catmull-Rom spline between `p1` and `p2`.
+fn catmull_rom<V>(p0: V, p1: V, p2: V, p3: V, t: f32) -> V
+where
+    V: Copy
+        + std::ops::Add<Output = V>
+        + std::ops::Sub<Output = V>
+        + std::ops::Mul<f32, Output = V>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(x: f32, yaw: f32) -> Keyframe {
+        Keyframe {
+            position: Point3::new(x, 1.0, -2.5),
+            yaw: Rad(yaw),
+            pitch: Rad(-0.25),
+            fovy: Rad(0.8),
+        }
+    }
+
+    fn assert_near(a: Keyframe, b: Keyframe) {
+        assert!(
+            (a.position - b.position).magnitude() < 1e-5,
+            "{} != {}",
+            a,
+            b
+        );
+        for (a, b) in [(a.yaw, b.yaw), (a.pitch, b.pitch), (a.fovy, b.fovy)] {
+            assert!((a - b).0.abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn path(keyframes: Vec<Keyframe>, looping: bool) -> CameraPath {
+        CameraPath {
+            keyframes,
+            looping,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn keyframe_round_trips_through_text() {
+        let keyframe = keyframe(123.456, -7.0625);
+        assert_eq!(keyframe.to_string().parse::<Keyframe>().unwrap(), keyframe);
+        assert!("1 2 3 4 5".parse::<Keyframe>().is_err());
+        assert!("1 2 3 4 5 6 7".parse::<Keyframe>().is_err());
+        assert!("1 2 3 yaw 5 6".parse::<Keyframe>().is_err());
+    }
+
+    #[test]
+    fn passes_through_keyframes() {
+        let path = path(
+            vec![keyframe(0.0, 0.0), keyframe(1.0, 0.5), keyframe(3.0, 1.0)],
+            false,
+        );
+        assert_near(path.sample(0.0).unwrap(), path.keyframes[0]);
+        assert_near(path.sample(0.5).unwrap(), path.keyframes[1]);
+        assert_near(path.sample(1.0).unwrap(), path.keyframes[2]);
+    }
+
+    #[test]
+    fn looping_closes_the_path() {
+        let path = path(
+            vec![keyframe(0.0, 0.0), keyframe(1.0, 0.5), keyframe(3.0, 1.0)],
+            true,
+        );
+        assert_near(path.sample(1.0 / 3.0).unwrap(), path.keyframes[1]);
+        assert_near(path.sample(2.0 / 3.0).unwrap(), path.keyframes[2]);
+        assert_near(path.sample(1.0).unwrap(), path.keyframes[0]);
+        // Halfway back to the start, not jumping there
+        let closing = path.sample(5.0 / 6.0).unwrap();
+        assert!(closing.position.x > 0.5 && closing.position.x < 2.5);
+    }
+
+    #[test]
+    fn load_unwraps_yaw() {
+        let file = std::env::temp_dir().join(format!("{}-path.txt", std::process::id()));
+        let mut saved = path(vec![keyframe(0.0, 3.0), keyframe(1.0, -3.0)], false);
+        saved.save(&file).unwrap();
+        saved.load(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        // The short way from 3 to -3 radians is across PI
+        assert!((saved.keyframes[1].yaw.0 - (2.0 * PI - 3.0)).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,178 @@
+use crate::camera;
+use cgmath::SquareMatrix;
+
+const WORKGROUP_SIZE: u32 = 64;
+/// Clusters along x, y and depth.
+pub const GRID_SIZE: [u32; 3] = [16, 9, 24];
+/// Lights beyond this many in one cluster are ignored.
+pub const MAX_LIGHTS_PER_CLUSTER: u32 = 128;
+const CLUSTER_COUNT: u32 = GRID_SIZE[0] * GRID_SIZE[1] * GRID_SIZE[2];
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClusterParams {
+    view: [[f32; 4]; 4],
+    inv_proj: [[f32; 4]; 4],
+    screen_size: [f32; 2],
+    znear: f32,
+    zfar: f32,
+    grid: [u32; 3],
+    max_lights: u32,
+}
+
+/// Clustered light culling for the forward path. A compute pass bins the
+/// point lights into a grid of froxels between `Projection::znear` and
+/// `zfar`, so fragments only loop over the lights of their own cluster. The
+/// last slice reaches on to infinity, for reverse-Z projections which draw
+/// past `zfar`.
+pub struct LightClusters {
+    pipeline: egui_wgpu::wgpu::ComputePipeline,
+    compute_bind_group: egui_wgpu::wgpu::BindGroup,
+    params_buffer: egui_wgpu::wgpu::Buffer,
+    /// The grid as read by the scene shader at group 3.
+    pub bind_group_layout: egui_wgpu::wgpu::BindGroupLayout,
+    pub bind_group: egui_wgpu::wgpu::BindGroup,
+}
+
+impl LightClusters {
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        light_layout: &egui_wgpu::wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(egui_wgpu::wgpu::include_wgsl!("cluster.wgsl"));
+
+        let params_buffer = device.create_buffer(&egui_wgpu::wgpu::BufferDescriptor {
+            label: Some("Cluster Params Buffer"),
+            size: std::mem::size_of::<ClusterParams>() as egui_wgpu::wgpu::BufferAddress,
+            usage: egui_wgpu::wgpu::BufferUsages::UNIFORM | egui_wgpu::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let cluster_buffer = device.create_buffer(&egui_wgpu::wgpu::BufferDescriptor {
+            label: Some("Cluster Buffer"),
+            size: (CLUSTER_COUNT * (MAX_LIGHTS_PER_CLUSTER + 1)) as egui_wgpu::wgpu::BufferAddress
+                * std::mem::size_of::<u32>() as egui_wgpu::wgpu::BufferAddress,
+            usage: egui_wgpu::wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        // The compute pass writes the grid which the fragment shader reads,
+        // which needs two layouts over the same buffers
+        let layout = |label, visibility, read_only| {
+            device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    egui_wgpu::wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility,
+                        ty: egui_wgpu::wgpu::BindingType::Buffer {
+                            ty: egui_wgpu::wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    egui_wgpu::wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility,
+                        ty: egui_wgpu::wgpu::BindingType::Buffer {
+                            ty: egui_wgpu::wgpu::BufferBindingType::Storage { read_only },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some(label),
+            })
+        };
+        let compute_layout = layout(
+            "cluster_compute_bind_group_layout",
+            egui_wgpu::wgpu::ShaderStages::COMPUTE,
+            false,
+        );
+        let bind_group_layout = layout(
+            "cluster_bind_group_layout",
+            egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+            true,
+        );
+        let bind_group = |label, layout| {
+            device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    egui_wgpu::wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                    egui_wgpu::wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: cluster_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some(label),
+            })
+        };
+        let compute_bind_group = bind_group("cluster_compute_bind_group", &compute_layout);
+        let render_bind_group = bind_group("cluster_bind_group", &bind_group_layout);
+
+        let pipeline_layout =
+            device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+                label: Some("Cluster Pipeline Layout"),
+                bind_group_layouts: &[&compute_layout, light_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline =
+            device.create_compute_pipeline(&egui_wgpu::wgpu::ComputePipelineDescriptor {
+                label: Some("Cluster Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "cs_main",
+            });
+
+        Self {
+            pipeline,
+            compute_bind_group,
+            params_buffer,
+            bind_group_layout,
+            bind_group: render_bind_group,
+        }
+    }
+
+    /// Records the pass assigning the lights in `light_bind_group` to the
+    /// clusters of the view seen through `camera` and `projection` on a
+    /// `width` by `height` target.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cull(
+        &self,
+        queue: &egui_wgpu::wgpu::Queue,
+        encoder: &mut egui_wgpu::wgpu::CommandEncoder,
+        camera: &camera::Camera,
+        projection: &dyn camera::Projection,
+        width: u32,
+        height: u32,
+        light_bind_group: &egui_wgpu::wgpu::BindGroup,
+    ) {
+        let params = ClusterParams {
+            view: camera.calc_matrix().into(),
+            inv_proj: projection
+                .calc_matrix()
+                .invert()
+                .unwrap_or(cgmath::Matrix4::identity())
+                .into(),
+            screen_size: [width as f32, height as f32],
+            znear: projection.znear(),
+            zfar: projection.zfar(),
+            grid: GRID_SIZE,
+            max_lights: MAX_LIGHTS_PER_CLUSTER,
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        let mut compute_pass =
+            encoder.begin_compute_pass(&egui_wgpu::wgpu::ComputePassDescriptor {
+                label: Some("Light Cluster Pass"),
+                timestamp_writes: None,
+            });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
+        compute_pass.set_bind_group(1, light_bind_group, &[]);
+        compute_pass.dispatch_workgroups(CLUSTER_COUNT.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+}
@@ -0,0 +1,113 @@
+// Light clustering compute shader
+//
+// Splits the view frustum into a grid of clusters, tiles on screen times
+// exponentially spaced slices in depth, and lists the point lights reaching
+// into each of them. The last slice goes on past `zfar`, for projections
+// without a far plane. Every cluster owns `max_lights + 1` entries of
+// `clusters`, the light count followed by the light indices.
+
+struct ClusterParams {
+    view: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    screen_size: vec2<f32>,
+    znear: f32,
+    zfar: f32,
+    grid: vec3<u32>,
+    max_lights: u32,
+}
+
+struct PointLight {
+    position: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+}
+
+struct LightParams {
+    sun_direction: vec3<f32>,
+    light_count: u32,
+    sun_color: vec3<f32>,
+    ambient: vec3<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> params: ClusterParams;
+@group(0) @binding(1)
+var<storage, read_write> clusters: array<u32>;
+
+@group(1) @binding(0)
+var<uniform> light_params: LightParams;
+@group(1) @binding(1)
+var<storage, read> point_lights: array<PointLight>;
+
+fn unproject(uv: vec2<f32>, ndc_depth: f32) -> vec3<f32> {
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, ndc_depth, 1.0);
+    let position = params.inv_proj * ndc;
+    return position.xyz / position.w;
+}
+
+// View space point seen at a point on screen at a distance from the camera.
+// Goes through the line of sight rather than a ray from the eye, so that it
+// holds for orthographic projections too.
+fn view_point(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let a = unproject(uv, 0.25);
+    let b = unproject(uv, 0.75);
+    return mix(a, b, (-depth - a.z) / (b.z - a.z));
+}
+
+// Distance from the camera at which a depth slice starts
+fn slice_depth(slice: u32) -> f32 {
+    return params.znear * pow(params.zfar / params.znear, f32(slice) / f32(params.grid.z));
+}
+
+struct Bounds {
+    min: vec3<f32>,
+    max: vec3<f32>,
+}
+
+// Box around a tile between two distances from the camera. Opposite corners
+// of the tile are enough to bound it, as the cluster spans the same frustum
+// at every depth.
+fn tile_bounds(uv_a: vec2<f32>, uv_b: vec2<f32>, near: f32, far: f32) -> Bounds {
+    let a_near = view_point(uv_a, near);
+    let a_far = view_point(uv_a, far);
+    let b_near = view_point(uv_b, near);
+    let b_far = view_point(uv_b, far);
+    return Bounds(min(min(a_near, a_far), min(b_near, b_far)), max(max(a_near, a_far), max(b_near, b_far)));
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    let grid = params.grid;
+    if index >= grid.x * grid.y * grid.z {
+        return;
+    }
+    let cell = vec3<u32>(index % grid.x, index / grid.x % grid.y, index / (grid.x * grid.y));
+
+    let uv_a = vec2<f32>(cell.xy) / vec2<f32>(grid.xy);
+    let uv_b = vec2<f32>(cell.xy + 1u) / vec2<f32>(grid.xy);
+    let near = slice_depth(cell.z);
+    let far = slice_depth(cell.z + 1u);
+    let last_slice = cell.z + 1u == grid.z;
+    let bounds = tile_bounds(uv_a, uv_b, near, far);
+
+    let base = index * (params.max_lights + 1u);
+    var count = 0u;
+    for (var i = 0u; i < light_params.light_count && count < params.max_lights; i++) {
+        let light = point_lights[i];
+        let center = (params.view * vec4<f32>(light.position, 1.0)).xyz;
+        // The last slice is stretched out to as far as the light reaches, so
+        // that fragments beyond `zfar`, which fall into it, are still lit
+        var light_bounds = bounds;
+        let reach = -center.z + light.range;
+        if last_slice && reach > far {
+            light_bounds = tile_bounds(uv_a, uv_b, near, reach);
+        }
+        let offset = clamp(center, light_bounds.min, light_bounds.max) - center;
+        if dot(offset, offset) < light.range * light.range {
+            clusters[base + 1u + count] = i;
+            count++;
+        }
+    }
+    clusters[base] = count;
+}
@@ -0,0 +1,271 @@
+use crate::{camera, instance, lod};
+use egui_wgpu::wgpu::util::DeviceExt;
+use tracing::trace;
+
+const WORKGROUP_SIZE: u32 = 64;
+const INDIRECT_ARGS_SIZE: usize =
+    std::mem::size_of::<egui_wgpu::wgpu::util::DrawIndexedIndirectArgs>();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CullingMode {
+    Off,
+    /// Instances are tested on the CPU and only the visible ones are uploaded.
+    Cpu,
+    /// Every instance is uploaded and tested in a compute pass, see `GpuCuller`.
+    Gpu,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullParams {
+    planes: [[f32; 4]; 6],
+    center: [f32; 3],
+    radius: f32,
+    camera_position: [f32; 3],
+    instance_count: u32,
+    lod_distances: [f32; lod::MAX_LOD_LEVELS],
+    lod_count: u32,
+    fade_band: f32,
+    region_size: u32,
+    _padding: u32,
+}
+
+/// Frustum culling and level of detail selection on the GPU. A compute pass
+/// compacts the visible instances into one region of `visible_buffer` per
+/// level and writes their counts into one indirect draw per level in
+/// `indirect_buffer`, which are then drawn with `draw_indexed_indirect`.
+pub struct GpuCuller {
+    pipeline: egui_wgpu::wgpu::ComputePipeline,
+    bind_group_layout: egui_wgpu::wgpu::BindGroupLayout,
+    bind_group: egui_wgpu::wgpu::BindGroup,
+    params_buffer: egui_wgpu::wgpu::Buffer,
+    pub visible_buffer: egui_wgpu::wgpu::Buffer,
+    pub indirect_buffer: egui_wgpu::wgpu::Buffer,
+    capacity: usize,
+}
+
+impl GpuCuller {
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        instance_buffer: &instance::InstanceBuffer,
+    ) -> Self {
+        let shader = device.create_shader_module(egui_wgpu::wgpu::include_wgsl!("culling.wgsl"));
+
+        let storage_entry = |binding, read_only| egui_wgpu::wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: egui_wgpu::wgpu::ShaderStages::COMPUTE,
+            ty: egui_wgpu::wgpu::BindingType::Buffer {
+                ty: egui_wgpu::wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout =
+            device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    egui_wgpu::wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: egui_wgpu::wgpu::ShaderStages::COMPUTE,
+                        ty: egui_wgpu::wgpu::BindingType::Buffer {
+                            ty: egui_wgpu::wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    storage_entry(1, true),
+                    storage_entry(2, false),
+                    storage_entry(3, false),
+                ],
+                label: Some("culling_bind_group_layout"),
+            });
+
+        let pipeline_layout =
+            device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+                label: Some("Culling Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline =
+            device.create_compute_pipeline(&egui_wgpu::wgpu::ComputePipelineDescriptor {
+                label: Some("Culling Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "cs_main",
+            });
+
+        let params_buffer = device.create_buffer(&egui_wgpu::wgpu::BufferDescriptor {
+            label: Some("Culling Params Buffer"),
+            size: std::mem::size_of::<CullParams>() as egui_wgpu::wgpu::BufferAddress,
+            usage: egui_wgpu::wgpu::BufferUsages::UNIFORM | egui_wgpu::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let indirect_buffer =
+            device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
+                label: Some("Culling Indirect Buffer"),
+                contents: &[0; INDIRECT_ARGS_SIZE * lod::MAX_LOD_LEVELS],
+                usage: egui_wgpu::wgpu::BufferUsages::STORAGE
+                    | egui_wgpu::wgpu::BufferUsages::INDIRECT
+                    | egui_wgpu::wgpu::BufferUsages::COPY_DST,
+            });
+
+        let capacity = instance_buffer.capacity();
+        let visible_buffer = Self::create_visible_buffer(device, capacity);
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &params_buffer,
+            instance_buffer,
+            &visible_buffer,
+            &indirect_buffer,
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            params_buffer,
+            visible_buffer,
+            indirect_buffer,
+            capacity,
+        }
+    }
+
+    fn create_visible_buffer(
+        device: &egui_wgpu::wgpu::Device,
+        capacity: usize,
+    ) -> egui_wgpu::wgpu::Buffer {
+        device.create_buffer(&egui_wgpu::wgpu::BufferDescriptor {
+            label: Some("Visible Instance Buffer"),
+            size: (capacity * lod::MAX_LOD_LEVELS * std::mem::size_of::<instance::InstanceRaw>())
+                as egui_wgpu::wgpu::BufferAddress,
+            usage: egui_wgpu::wgpu::BufferUsages::STORAGE | egui_wgpu::wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        device: &egui_wgpu::wgpu::Device,
+        layout: &egui_wgpu::wgpu::BindGroupLayout,
+        params_buffer: &egui_wgpu::wgpu::Buffer,
+        instance_buffer: &instance::InstanceBuffer,
+        visible_buffer: &egui_wgpu::wgpu::Buffer,
+        indirect_buffer: &egui_wgpu::wgpu::Buffer,
+    ) -> egui_wgpu::wgpu::BindGroup {
+        device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: instance_buffer.buffer.as_entire_binding(),
+                },
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: visible_buffer.as_entire_binding(),
+                },
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: indirect_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("culling_bind_group"),
+        })
+    }
+
+    /// Records the culling pass for instances drawn with `lod`, which switches
+    /// levels at `lod_distances` as returned by `LodMesh::switch_distances`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cull(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        queue: &egui_wgpu::wgpu::Queue,
+        encoder: &mut egui_wgpu::wgpu::CommandEncoder,
+        instance_buffer: &instance::InstanceBuffer,
+        frustum: &camera::Frustum,
+        camera_position: cgmath::Point3<f32>,
+        lod: &lod::LodMesh,
+        lod_distances: &[f32; lod::MAX_LOD_LEVELS],
+    ) {
+        // The instance buffer only gets reallocated when it grows, in which
+        // case the bind group points at a dead buffer.
+        if instance_buffer.capacity() != self.capacity {
+            self.capacity = instance_buffer.capacity();
+            self.visible_buffer = Self::create_visible_buffer(device, self.capacity);
+            self.bind_group = Self::create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.params_buffer,
+                instance_buffer,
+                &self.visible_buffer,
+                &self.indirect_buffer,
+            );
+            trace!("Culling buffers resized to {} instances", self.capacity);
+        }
+
+        let sphere = &lod.base().sphere;
+        let params = CullParams {
+            planes: frustum.planes.map(Into::into),
+            center: sphere.center.into(),
+            radius: sphere.radius,
+            camera_position: camera_position.into(),
+            instance_count: instance_buffer.len() as u32,
+            lod_distances: *lod_distances,
+            lod_count: lod.active_levels() as u32,
+            fade_band: lod.fade_band,
+            region_size: self.capacity as u32,
+            _padding: 0,
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        let mut draws = Vec::with_capacity(INDIRECT_ARGS_SIZE * lod::MAX_LOD_LEVELS);
+        for i in 0..lod::MAX_LOD_LEVELS {
+            let index_count = lod.levels.get(i).map_or(0, |level| level.mesh.num_indices);
+            draws.extend_from_slice(
+                egui_wgpu::wgpu::util::DrawIndexedIndirectArgs {
+                    index_count,
+                    instance_count: 0,
+                    first_index: 0,
+                    base_vertex: 0,
+                    first_instance: 0,
+                }
+                .as_bytes(),
+            );
+        }
+        queue.write_buffer(&self.indirect_buffer, 0, &draws);
+
+        if instance_buffer.is_empty() {
+            return;
+        }
+
+        let mut compute_pass =
+            encoder.begin_compute_pass(&egui_wgpu::wgpu::ComputePassDescriptor {
+                label: Some("Culling Pass"),
+                timestamp_writes: None,
+            });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            (instance_buffer.len() as u32).div_ceil(WORKGROUP_SIZE),
+            1,
+            1,
+        );
+    }
+
+    /// The compacted instances of a single level.
+    pub fn visible_instances(&self, level: usize) -> egui_wgpu::wgpu::BufferSlice<'_> {
+        let region = (self.capacity * std::mem::size_of::<instance::InstanceRaw>())
+            as egui_wgpu::wgpu::BufferAddress;
+        self.visible_buffer
+            .slice(region * level as u64..region * (level as u64 + 1))
+    }
+
+    /// Offset of the indirect draw of a single level in `indirect_buffer`.
+    pub fn indirect_offset(level: usize) -> egui_wgpu::wgpu::BufferAddress {
+        (level * INDIRECT_ARGS_SIZE) as egui_wgpu::wgpu::BufferAddress
+    }
+}
@@ -0,0 +1,94 @@
+// Frustum culling compute shader
+//
+// Tests every instance's bounding sphere against the camera frustum, picks
+// a level of detail for the visible ones and appends them to that level's
+// region of a compacted buffer, counting them into the instance_count of the
+// level's indexed indirect draw. Instances in a cross-fade are appended to
+// both levels.
+
+struct Instance {
+    model: mat4x4<f32>,
+    normal_0: vec4<f32>,
+    normal_1: vec4<f32>,
+    normal_2: vec4<f32>,
+    tint: vec4<f32>,
+    custom: vec4<f32>,
+    layer: u32,
+    lod_fade: f32,
+    _padding: vec2<f32>,
+}
+
+struct Params {
+    planes: array<vec4<f32>, 6>,
+    center: vec3<f32>,
+    radius: f32,
+    camera_position: vec3<f32>,
+    instance_count: u32,
+    lod_distances: vec4<f32>,
+    lod_count: u32,
+    fade_band: f32,
+    region_size: u32,
+}
+
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var<storage, read> instances: array<Instance>;
+@group(0) @binding(2)
+var<storage, read_write> visible: array<Instance>;
+@group(0) @binding(3)
+var<storage, read_write> draws: array<DrawIndexedIndirect, 4>;
+
+fn append(level: u32, instance: Instance) {
+    let slot = atomicAdd(&draws[level].instance_count, 1u);
+    visible[level * params.region_size + slot] = instance;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.instance_count {
+        return;
+    }
+
+    let instance = instances[index];
+    // Instances are never scaled, so only the sphere's center moves
+    let center = (instance.model * vec4<f32>(params.center, 1.0)).xyz;
+    for (var i = 0u; i < 6u; i++) {
+        let plane = params.planes[i];
+        if dot(plane.xyz, center) + plane.w < -params.radius {
+            return;
+        }
+    }
+
+    let distance = length(center - params.camera_position);
+    var level = params.lod_count - 1u;
+    for (var i = 0u; i + 1u < params.lod_count; i++) {
+        if distance <= params.lod_distances[i] {
+            level = i;
+            break;
+        }
+    }
+
+    var fade = 0.0;
+    if level + 1u < params.lod_count && params.fade_band > 0.0 {
+        let band = params.lod_distances[level] * params.fade_band;
+        fade = max((distance - (params.lod_distances[level] - band)) / band, 0.0);
+    }
+
+    var out = instance;
+    out.lod_fade = fade;
+    append(level, out);
+    if fade > 0.0 {
+        out.lod_fade = -fade;
+        append(level + 1u, out);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::{camera, hdr, state, texture, transparency};
+
+const ALBEDO_FORMAT: egui_wgpu::wgpu::TextureFormat =
+    egui_wgpu::wgpu::TextureFormat::Rgba8UnormSrgb;
+const NORMAL_FORMAT: egui_wgpu::wgpu::TextureFormat = egui_wgpu::wgpu::TextureFormat::Rgba16Float;
+const MATERIAL_FORMAT: egui_wgpu::wgpu::TextureFormat = egui_wgpu::wgpu::TextureFormat::Rgba8Unorm;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderPath {
+    /// Every fragment is lit as it is drawn, which also shades fragments that
+    /// are overdrawn later on.
+    Forward,
+    /// Surfaces are written into a G-buffer first and lit once per pixel in a
+    /// full screen pass. Doesn't support MSAA.
+    Deferred,
+}
+
+/// G-buffer targets and the pipelines filling and lighting them. The depth
+/// buffer is the scene's own, so it needs to be single sampled.
+pub struct DeferredRenderer {
+    gbuffer_pipeline: egui_wgpu::wgpu::RenderPipeline,
+    lighting_pipeline: egui_wgpu::wgpu::RenderPipeline,
+    gbuffer_layout: egui_wgpu::wgpu::BindGroupLayout,
+    albedo: texture::Texture,
+    normal: texture::Texture,
+    material: texture::Texture,
+    /// Missing while the depth buffer is multisampled.
+    bind_group: Option<egui_wgpu::wgpu::BindGroup>,
+    depth_mode: camera::DepthMode,
+}
+
+impl DeferredRenderer {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        config: &egui_wgpu::wgpu::SurfaceConfiguration,
+        scene_layout: &egui_wgpu::wgpu::PipelineLayout,
+        scene_shader: &egui_wgpu::wgpu::ShaderModule,
+        camera_layout: &egui_wgpu::wgpu::BindGroupLayout,
+        light_layout: &egui_wgpu::wgpu::BindGroupLayout,
+        depth_texture: &texture::Texture,
+        sample_count: u32,
+        depth_mode: camera::DepthMode,
+    ) -> Self {
+        let gbuffer_entry = |binding| egui_wgpu::wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+            ty: egui_wgpu::wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: egui_wgpu::wgpu::TextureViewDimension::D2,
+                sample_type: egui_wgpu::wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        };
+        let gbuffer_layout =
+            device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    gbuffer_entry(0),
+                    gbuffer_entry(1),
+                    gbuffer_entry(2),
+                    gbuffer_entry(3),
+                ],
+                label: Some("gbuffer_bind_group_layout"),
+            });
+
+        let gbuffer_pipeline =
+            create_gbuffer_pipeline(device, scene_layout, scene_shader, depth_mode);
+
+        let shader = crate::light::create_shader_module(
+            device,
+            "deferred.wgsl",
+            include_str!("deferred.wgsl"),
+        );
+        let lighting_layout =
+            device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+                label: Some("Deferred Lighting Pipeline Layout"),
+                bind_group_layouts: &[&gbuffer_layout, camera_layout, light_layout],
+                push_constant_ranges: &[],
+            });
+        let lighting_pipeline =
+            device.create_render_pipeline(&egui_wgpu::wgpu::RenderPipelineDescriptor {
+                label: Some("Deferred Lighting Pipeline"),
+                layout: Some(&lighting_layout),
+                vertex: egui_wgpu::wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(egui_wgpu::wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(egui_wgpu::wgpu::ColorTargetState {
+                        format: hdr::HDR_FORMAT,
+                        blend: None,
+                        write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: egui_wgpu::wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: egui_wgpu::wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        let (albedo, normal, material) = create_targets(device, config);
+        let mut deferred = Self {
+            gbuffer_pipeline,
+            lighting_pipeline,
+            gbuffer_layout,
+            albedo,
+            normal,
+            material,
+            bind_group: None,
+            depth_mode,
+        };
+        deferred.create_bind_group(device, depth_texture, sample_count);
+        deferred
+    }
+
+    fn create_bind_group(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        depth_texture: &texture::Texture,
+        sample_count: u32,
+    ) {
+        fn view(binding: u32, texture: &texture::Texture) -> egui_wgpu::wgpu::BindGroupEntry<'_> {
+            egui_wgpu::wgpu::BindGroupEntry {
+                binding,
+                resource: egui_wgpu::wgpu::BindingResource::TextureView(&texture.view),
+            }
+        }
+        self.bind_group = (sample_count == 1).then(|| {
+            device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+                layout: &self.gbuffer_layout,
+                entries: &[
+                    view(0, &self.albedo),
+                    view(1, &self.normal),
+                    view(2, &self.material),
+                    view(3, depth_texture),
+                ],
+                label: Some("gbuffer_bind_group"),
+            })
+        });
+    }
+
+    pub fn resize(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        config: &egui_wgpu::wgpu::SurfaceConfiguration,
+        depth_texture: &texture::Texture,
+        sample_count: u32,
+    ) {
+        (self.albedo, self.normal, self.material) = create_targets(device, config);
+        self.create_bind_group(device, depth_texture, sample_count);
+    }
+
+    /// Recreates the G-buffer pipeline for testing against `depth_mode`.
+    pub fn set_depth_mode(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        scene_layout: &egui_wgpu::wgpu::PipelineLayout,
+        scene_shader: &egui_wgpu::wgpu::ShaderModule,
+        depth_mode: camera::DepthMode,
+    ) {
+        self.depth_mode = depth_mode;
+        self.gbuffer_pipeline =
+            create_gbuffer_pipeline(device, scene_layout, scene_shader, depth_mode);
+    }
+
+    /// Starts the pass writing into the G-buffer with its pipeline set. The
+    /// scene's bind groups still need to be set before drawing.
+    pub fn begin_gbuffer_pass<'a>(
+        &'a self,
+        encoder: &'a mut egui_wgpu::wgpu::CommandEncoder,
+        depth: &'a egui_wgpu::wgpu::TextureView,
+    ) -> egui_wgpu::wgpu::RenderPass<'a> {
+        let target = |texture: &'a texture::Texture| {
+            Some(egui_wgpu::wgpu::RenderPassColorAttachment {
+                view: &texture.view,
+                resolve_target: None,
+                ops: egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Clear(egui_wgpu::wgpu::Color::TRANSPARENT),
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                },
+            })
+        };
+        let mut render_pass = encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
+            label: Some("G-Buffer Pass"),
+            color_attachments: &[
+                target(&self.albedo),
+                target(&self.normal),
+                target(&self.material),
+            ],
+            depth_stencil_attachment: Some(egui_wgpu::wgpu::RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Clear(self.depth_mode.far_depth()),
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.gbuffer_pipeline);
+        render_pass
+    }
+
+    /// Lights the G-buffer into `output`, filling the background with
+    /// `clear_color`.
+    pub fn light(
+        &self,
+        encoder: &mut egui_wgpu::wgpu::CommandEncoder,
+        output: &egui_wgpu::wgpu::TextureView,
+        camera_bind_group: &egui_wgpu::wgpu::BindGroup,
+        light_bind_group: &egui_wgpu::wgpu::BindGroup,
+        clear_color: egui_wgpu::wgpu::Color,
+    ) {
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
+            label: Some("Deferred Lighting Pass"),
+            color_attachments: &[Some(egui_wgpu::wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Clear(clear_color),
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.lighting_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, light_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_gbuffer_pipeline(
+    device: &egui_wgpu::wgpu::Device,
+    scene_layout: &egui_wgpu::wgpu::PipelineLayout,
+    scene_shader: &egui_wgpu::wgpu::ShaderModule,
+    depth_mode: camera::DepthMode,
+) -> egui_wgpu::wgpu::RenderPipeline {
+    state::create_render_pipeline(
+        device,
+        scene_layout,
+        scene_shader,
+        "fs_gbuffer",
+        &[ALBEDO_FORMAT, NORMAL_FORMAT, MATERIAL_FORMAT].map(|format| {
+            Some(egui_wgpu::wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
+            })
+        }),
+        Some(transparency::AlphaMode::Opaque),
+        1,
+        depth_mode,
+    )
+}
+
+fn create_targets(
+    device: &egui_wgpu::wgpu::Device,
+    config: &egui_wgpu::wgpu::SurfaceConfiguration,
+) -> (texture::Texture, texture::Texture, texture::Texture) {
+    let target = |format, label| {
+        texture::Texture::create_render_target(device, config.width, config.height, format, label)
+    };
+    (
+        target(ALBEDO_FORMAT, "gbuffer_albedo"),
+        target(NORMAL_FORMAT, "gbuffer_normal"),
+        target(MATERIAL_FORMAT, "gbuffer_material"),
+    )
+}
@@ -0,0 +1,56 @@
+// Deferred lighting pass
+//
+// G-buffer layout:
+//   albedo:   rgb albedo, a unused
+//   normal:   xyz world space normal, zero where nothing was drawn
+//   material: r specular, g shininess / MAX_SHININESS
+// World positions are reconstructed from the depth buffer.
+
+@group(0) @binding(0)
+var t_albedo: texture_2d<f32>;
+@group(0) @binding(1)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(2)
+var t_material: texture_2d<f32>;
+@group(0) @binding(3)
+var t_depth: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// A single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.clip_position.xy);
+    let normal = textureLoad(t_normal, coord, 0).xyz;
+    // Nothing was drawn here, keep the clear color. Checked on the normal as
+    // the depth of the far plane depends on the depth mode.
+    if dot(normal, normal) == 0.0 {
+        discard;
+    }
+    let depth = textureLoad(t_depth, coord, 0).r;
+
+    let size = vec2<f32>(textureDimensions(t_depth));
+    let uv = in.clip_position.xy / size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = camera.inv_view_proj * ndc;
+
+    let albedo = textureLoad(t_albedo, coord, 0);
+    let material = textureLoad(t_material, coord, 0);
+    var surface: Surface;
+    surface.position = world.xyz / world.w;
+    surface.normal = normalize(normal);
+    surface.albedo = albedo.rgb;
+    surface.specular = material.r;
+    surface.shininess = material.g * MAX_SHININESS;
+    return vec4<f32>(shade(surface), 1.0);
+}
@@ -0,0 +1,126 @@
+use cgmath::*;
+use gilrs::{Axis, Button, GamepadId, Gilrs};
+
+/// State of the gamepad last used, with deadzones applied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GamepadInput {
+    /// Right and up, from -1 to 1.
+    pub left_stick: Vector2<f32>,
+    pub right_stick: Vector2<f32>,
+    /// From 0 to 1.
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+    /// Left stick pressed in.
+    pub sprinting: bool,
+}
+
+impl Default for GamepadInput {
+    fn default() -> Self {
+        Self {
+            left_stick: Vector2::zero(),
+            right_stick: Vector2::zero(),
+            left_trigger: 0.0,
+            right_trigger: 0.0,
+            sprinting: false,
+        }
+    }
+}
+
+/// Reads whichever gamepad last sent an event, so that anyone on the couch
+/// can pick one up and take over.
+pub struct Gamepads {
+    gilrs: Gilrs,
+    active: Option<GamepadId>,
+    /// Stick and trigger travel ignored around rest, as a fraction of the
+    /// full range.
+    pub deadzone: f32,
+    /// Scales how fast the right stick turns the camera.
+    pub sensitivity: f32,
+    /// Flips the right stick's vertical axis.
+    pub invert_y: bool,
+}
+
+impl Gamepads {
+    pub fn new() -> anyhow::Result<Self> {
+        let gilrs = Gilrs::new().map_err(|e| anyhow::anyhow!("{}", e))?;
+        let active = gilrs.gamepads().next().map(|(id, _)| id);
+        Ok(Self {
+            gilrs,
+            active,
+            deadzone: 0.15,
+            sensitivity: 1.0,
+            invert_y: false,
+        })
+    }
+
+    /// Name of the gamepad being read, if any is connected.
+    pub fn active_name(&self) -> Option<String> {
+        self.active
+            .and_then(|id| self.gilrs.connected_gamepad(id))
+            .map(|gamepad| gamepad.name().to_string())
+    }
+
+    /// Handles pending events and reads the active gamepad. Call once per
+    /// frame.
+    pub fn poll(&mut self) -> GamepadInput {
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            match event {
+                gilrs::EventType::Connected => {
+                    tracing::info!("Gamepad connected: {}", self.gilrs.gamepad(id).name());
+                    self.active = Some(id);
+                }
+                gilrs::EventType::Disconnected => {
+                    tracing::info!("Gamepad disconnected: {}", self.gilrs.gamepad(id).name());
+                    if self.active == Some(id) {
+                        self.active = self.gilrs.gamepads().next().map(|(id, _)| id);
+                    }
+                }
+                _ => self.active = Some(id),
+            }
+        }
+
+        let Some(gamepad) = self.active.and_then(|id| self.gilrs.connected_gamepad(id)) else {
+            return GamepadInput::default();
+        };
+        let stick = |x, y| {
+            apply_deadzone(
+                Vector2::new(gamepad.value(x), gamepad.value(y)),
+                self.deadzone,
+            )
+        };
+        // Triggers are analog buttons on most gamepads
+        let trigger = |button| {
+            let value = gamepad.button_data(button).map_or(0.0, |data| data.value());
+            rescale(value, self.deadzone)
+        };
+        let mut right_stick = stick(Axis::RightStickX, Axis::RightStickY) * self.sensitivity;
+        if self.invert_y {
+            right_stick.y = -right_stick.y;
+        }
+        GamepadInput {
+            left_stick: stick(Axis::LeftStickX, Axis::LeftStickY),
+            right_stick,
+            left_trigger: trigger(Button::LeftTrigger2),
+            right_trigger: trigger(Button::RightTrigger2),
+            sprinting: gamepad.is_pressed(Button::LeftThumb),
+        }
+    }
+}
+
+/// Radial deadzone, which unlike one per axis doesn't snap diagonal movement
+/// to the axes. The remaining travel is stretched to start from zero.
+fn apply_deadzone(stick: Vector2<f32>, deadzone: f32) -> Vector2<f32> {
+    let length = stick.magnitude();
+    if length <= deadzone {
+        return Vector2::zero();
+    }
+    stick * (rescale(length, deadzone) / length)
+}
+
+fn rescale(value: f32, deadzone: f32) -> f32 {
+    if value <= deadzone {
+        0.0
+    } else {
+        ((value - deadzone) / (1.0 - deadzone)).min(1.0)
+    }
+}
@@ -1,3 +1,5 @@
+use crate::instance::{self, Instance};
+use crate::postprocess::{Effect, EffectSettings};
 use egui::Context;
 use egui_wgpu::Renderer;
 use egui_winit::State;
@@ -42,6 +44,18 @@ impl EguiRenderer {
         let _ = self.state.on_window_event(window, event);
     }
 
+    /// Whether a text field has focus, in which case key presses are meant
+    /// for it rather than for camera controls.
+    pub fn wants_keyboard_input(&self) -> bool {
+        self.context.wants_keyboard_input()
+    }
+
+    /// Whether the pointer is over egui or dragging one of its widgets.
+    pub fn wants_pointer_input(&self) -> bool {
+        self.context.wants_pointer_input()
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn render(
         &mut self,
         device: &egui_wgpu::wgpu::Device,
@@ -56,7 +70,7 @@ impl EguiRenderer {
         let full_output = self.context.run(raw_input, |ui| run_ui(ui));
 
         self.state
-            .handle_platform_output(&window, full_output.platform_output);
+            .handle_platform_output(window, full_output.platform_output);
 
         let tris = self
             .context
@@ -90,3 +104,208 @@ impl EguiRenderer {
         }
     }
 }
+
+/// Debug window section for browsing and tweaking instances.
+pub struct InstanceEditor {
+    pub selected: Option<usize>,
+    pub per_row: u32,
+    /// Brings the selection into view in the list on the next frame.
+    scroll_to_selected: bool,
+}
+
+impl InstanceEditor {
+    pub fn new(per_row: u32) -> Self {
+        Self {
+            selected: None,
+            per_row,
+            scroll_to_selected: false,
+        }
+    }
+
+    /// Selects an instance picked outside of the list, scrolling it into view.
+    pub fn select(&mut self, index: Option<usize>) {
+        self.selected = index;
+        self.scroll_to_selected = index.is_some();
+    }
+
+    /// Draws the editor and returns true if `instances` was modified.
+    pub fn ui(&mut self, ui: &mut egui::Ui, instances: &mut Vec<Instance>) -> bool {
+        let mut changed = false;
+
+        ui.horizontal(|ui| {
+            ui.add(egui::Slider::new(&mut self.per_row, 1..=40).text("Per row"));
+            if ui.button("Regenerate grid").clicked() {
+                *instances = instance::grid(self.per_row);
+                self.selected = None;
+                changed = true;
+            }
+        });
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        let mut scroll_area = egui::ScrollArea::vertical()
+            .id_source("instance_list")
+            .max_height(150.0);
+        if let Some(index) = self.selected.filter(|_| self.scroll_to_selected) {
+            let spacing = ui.spacing().item_spacing.y;
+            scroll_area = scroll_area.vertical_scroll_offset(index as f32 * (row_height + spacing));
+            self.scroll_to_selected = false;
+        }
+        scroll_area.show_rows(ui, row_height, instances.len(), |ui, rows| {
+            for i in rows {
+                let position = instances[i].position;
+                let label = format!(
+                    "#{} ({:.1}, {:.1}, {:.1})",
+                    i, position.x, position.y, position.z
+                );
+                if ui
+                    .selectable_label(self.selected == Some(i), label)
+                    .clicked()
+                {
+                    self.selected = Some(i);
+                }
+            }
+        });
+
+        let Some(index) = self.selected.filter(|i| *i < instances.len()) else {
+            self.selected = None;
+            return changed;
+        };
+        let instance = &mut instances[index];
+
+        ui.label(format!("Selected: #{}", index));
+        ui.horizontal(|ui| {
+            ui.label("Position");
+            changed |= ui
+                .add(egui::DragValue::new(&mut instance.position.x).speed(0.1))
+                .changed();
+            changed |= ui
+                .add(egui::DragValue::new(&mut instance.position.y).speed(0.1))
+                .changed();
+            changed |= ui
+                .add(egui::DragValue::new(&mut instance.position.z).speed(0.1))
+                .changed();
+        });
+
+        // Only write the rotation back when it was edited, so that the
+        // quaternion -> euler -> quaternion round trip doesn't drift.
+        let euler = cgmath::Euler::from(instance.rotation);
+        let mut degrees = [
+            cgmath::Deg::from(euler.x).0,
+            cgmath::Deg::from(euler.y).0,
+            cgmath::Deg::from(euler.z).0,
+        ];
+        let mut rotation_changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Rotation");
+            for angle in degrees.iter_mut() {
+                rotation_changed |= ui
+                    .add(egui::DragValue::new(angle).speed(1.0).suffix("°"))
+                    .changed();
+            }
+        });
+        if rotation_changed {
+            instance.rotation = cgmath::Euler::new(
+                cgmath::Deg(degrees[0]),
+                cgmath::Deg(degrees[1]),
+                cgmath::Deg(degrees[2]),
+            )
+            .into();
+            changed = true;
+        }
+
+        // Below one, the instance is drawn in the transparent pass
+        changed |= ui
+            .add(egui::Slider::new(&mut instance.tint[3], 0.0..=1.0).text("Opacity"))
+            .changed();
+
+        if ui.button("Remove").clicked() {
+            instances.remove(index);
+            self.selected = None;
+            changed = true;
+        }
+
+        changed
+    }
+}
+
+/// Debug window section toggling, tweaking and reordering post processing
+/// effects.
+pub fn effect_stack(ui: &mut egui::Ui, effects: &mut [Effect]) {
+    let mut swap = None;
+    let last = effects.len().saturating_sub(1);
+
+    for (i, effect) in effects.iter_mut().enumerate() {
+        ui.push_id(i, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut effect.enabled, effect.settings.name());
+                if ui.add_enabled(i > 0, egui::Button::new("Up")).clicked() {
+                    swap = Some((i - 1, i));
+                }
+                if ui
+                    .add_enabled(i < last, egui::Button::new("Down"))
+                    .clicked()
+                {
+                    swap = Some((i, i + 1));
+                }
+            });
+            if !effect.enabled {
+                return;
+            }
+
+            ui.indent("settings", |ui| match &mut effect.settings {
+                EffectSettings::Bloom {
+                    threshold,
+                    intensity,
+                    radius,
+                } => {
+                    ui.add(egui::Slider::new(threshold, 0.0..=4.0).text("Threshold"));
+                    ui.add(egui::Slider::new(intensity, 0.0..=2.0).text("Intensity"));
+                    ui.add(egui::Slider::new(radius, 0.5..=4.0).text("Radius"));
+                }
+                EffectSettings::Fxaa {
+                    subpixel,
+                    edge_threshold,
+                    edge_threshold_min,
+                } => {
+                    ui.add(egui::Slider::new(subpixel, 0.0..=1.0).text("Subpixel"));
+                    ui.add(egui::Slider::new(edge_threshold, 0.063..=0.333).text("Edge threshold"));
+                    ui.add(
+                        egui::Slider::new(edge_threshold_min, 0.0..=0.1).text("Minimum threshold"),
+                    );
+                }
+                EffectSettings::Vignette {
+                    intensity,
+                    radius,
+                    smoothness,
+                } => {
+                    ui.add(egui::Slider::new(intensity, 0.0..=1.0).text("Intensity"));
+                    ui.add(egui::Slider::new(radius, 0.0..=1.5).text("Radius"));
+                    ui.add(egui::Slider::new(smoothness, 0.01..=1.0).text("Smoothness"));
+                }
+                EffectSettings::ChromaticAberration { strength } => {
+                    ui.add(egui::Slider::new(strength, 0.0..=20.0).text("Strength"));
+                }
+                EffectSettings::ColorGrading {
+                    intensity,
+                    contrast,
+                    saturation,
+                    temperature,
+                    tint,
+                } => {
+                    ui.add(egui::Slider::new(intensity, 0.0..=1.0).text("Intensity"));
+                    ui.add(egui::Slider::new(contrast, 0.5..=2.0).text("Contrast"));
+                    ui.add(egui::Slider::new(saturation, 0.0..=2.0).text("Saturation"));
+                    ui.add(egui::Slider::new(temperature, -1.0..=1.0).text("Temperature"));
+                    ui.add(egui::Slider::new(tint, -1.0..=1.0).text("Tint"));
+                }
+                EffectSettings::FilmGrain { intensity } => {
+                    ui.add(egui::Slider::new(intensity, 0.0..=0.5).text("Intensity"));
+                }
+            });
+        });
+    }
+
+    if let Some((a, b)) = swap {
+        effects.swap(a, b);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::texture;
+use egui_wgpu::wgpu::util::DeviceExt;
+
+/// Format the scene is rendered in before it gets tonemapped.
+pub const HDR_FORMAT: egui_wgpu::wgpu::TextureFormat = egui_wgpu::wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tonemapper {
+    /// Narkowicz's fit of the ACES filmic curve, contrasty and saturated.
+    Aces,
+    /// `x / (1 + x)`, which never clips but washes out highlights.
+    Reinhard,
+    /// Desaturates bright colors towards white instead of skewing their hue.
+    AgX,
+}
+
+impl Tonemapper {
+    pub const ALL: [Self; 3] = [Self::Aces, Self::Reinhard, Self::AgX];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Aces => "ACES",
+            Self::Reinhard => "Reinhard",
+            Self::AgX => "AgX",
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapUniform {
+    exposure: f32,
+    tonemapper: u32,
+    _padding: [u32; 2],
+}
+
+/// Offscreen `HDR_FORMAT` target the scene is drawn into, and the full screen
+/// pass mapping it onto the surface.
+pub struct HdrPipeline {
+    texture: texture::Texture,
+    pipeline: egui_wgpu::wgpu::RenderPipeline,
+    bind_group_layout: egui_wgpu::wgpu::BindGroupLayout,
+    bind_group: egui_wgpu::wgpu::BindGroup,
+    uniform_buffer: egui_wgpu::wgpu::Buffer,
+    pub exposure: f32,
+    pub tonemapper: Tonemapper,
+}
+
+impl HdrPipeline {
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        config: &egui_wgpu::wgpu::SurfaceConfiguration,
+    ) -> Self {
+        let texture = texture::Texture::create_render_target(
+            device,
+            config.width,
+            config.height,
+            HDR_FORMAT,
+            "hdr_texture",
+        );
+
+        let bind_group_layout =
+            device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    egui_wgpu::wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+                        ty: egui_wgpu::wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: egui_wgpu::wgpu::TextureViewDimension::D2,
+                            sample_type: egui_wgpu::wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    egui_wgpu::wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+                        ty: egui_wgpu::wgpu::BindingType::Sampler(
+                            egui_wgpu::wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                    egui_wgpu::wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+                        ty: egui_wgpu::wgpu::BindingType::Buffer {
+                            ty: egui_wgpu::wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("tonemap_bind_group_layout"),
+            });
+
+        let exposure = 1.0;
+        let tonemapper = Tonemapper::Aces;
+        let uniform_buffer =
+            device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
+                label: Some("Tonemap Buffer"),
+                contents: bytemuck::cast_slice(&[TonemapUniform {
+                    exposure,
+                    tonemapper: tonemapper as u32,
+                    _padding: [0; 2],
+                }]),
+                usage: egui_wgpu::wgpu::BufferUsages::UNIFORM
+                    | egui_wgpu::wgpu::BufferUsages::COPY_DST,
+            });
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, &texture, &uniform_buffer);
+
+        let shader = device.create_shader_module(egui_wgpu::wgpu::include_wgsl!("tonemap.wgsl"));
+        let pipeline_layout =
+            device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+                label: Some("Tonemap Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = device.create_render_pipeline(&egui_wgpu::wgpu::RenderPipelineDescriptor {
+            label: Some("Tonemap Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: egui_wgpu::wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(egui_wgpu::wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(egui_wgpu::wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: None,
+                    write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: egui_wgpu::wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: egui_wgpu::wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            texture,
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            uniform_buffer,
+            exposure,
+            tonemapper,
+        }
+    }
+
+    fn create_bind_group(
+        device: &egui_wgpu::wgpu::Device,
+        layout: &egui_wgpu::wgpu::BindGroupLayout,
+        texture: &texture::Texture,
+        uniform_buffer: &egui_wgpu::wgpu::Buffer,
+    ) -> egui_wgpu::wgpu::BindGroup {
+        device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: egui_wgpu::wgpu::BindingResource::TextureView(&texture.view),
+                },
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: egui_wgpu::wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("tonemap_bind_group"),
+        })
+    }
+
+    pub fn resize(&mut self, device: &egui_wgpu::wgpu::Device, width: u32, height: u32) {
+        self.texture = texture::Texture::create_render_target(
+            device,
+            width,
+            height,
+            HDR_FORMAT,
+            "hdr_texture",
+        );
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.texture,
+            &self.uniform_buffer,
+        );
+    }
+
+    /// The view the scene should be rendered into.
+    pub fn view(&self) -> &egui_wgpu::wgpu::TextureView {
+        &self.texture.view
+    }
+
+    pub fn texture(&self) -> &texture::Texture {
+        &self.texture
+    }
+
+    /// Tonemaps the HDR target into `output`, overwriting its contents.
+    pub fn process(
+        &self,
+        queue: &egui_wgpu::wgpu::Queue,
+        encoder: &mut egui_wgpu::wgpu::CommandEncoder,
+        output: &egui_wgpu::wgpu::TextureView,
+    ) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[TonemapUniform {
+                exposure: self.exposure,
+                tonemapper: self.tonemapper as u32,
+                _padding: [0; 2],
+            }]),
+        );
+
+        let mut pass = encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
+            label: Some("Tonemap Pass"),
+            color_attachments: &[Some(egui_wgpu::wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Clear(egui_wgpu::wgpu::Color::BLACK),
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::bounds;
+use egui_wgpu::wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct HighlightUniform {
+    model_view_proj: [[f32; 4]; 4],
+    min: [f32; 4],
+    max: [f32; 4],
+    color: [f32; 4],
+}
+
+/// Outlines a bounding box on top of the finished frame, marking the
+/// selected instance.
+pub struct Highlight {
+    /// Linear color, with alpha blending over the frame.
+    pub color: [f32; 4],
+    uniform_buffer: egui_wgpu::wgpu::Buffer,
+    bind_group: egui_wgpu::wgpu::BindGroup,
+    pipeline: egui_wgpu::wgpu::RenderPipeline,
+}
+
+impl Highlight {
+    pub fn new(device: &egui_wgpu::wgpu::Device, format: egui_wgpu::wgpu::TextureFormat) -> Self {
+        let layout = device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+            entries: &[egui_wgpu::wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: egui_wgpu::wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: egui_wgpu::wgpu::BindingType::Buffer {
+                    ty: egui_wgpu::wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("highlight_bind_group_layout"),
+        });
+        let uniform_buffer =
+            device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
+                label: Some("Highlight Buffer"),
+                contents: bytemuck::cast_slice(&[HighlightUniform {
+                    model_view_proj: [[0.0; 4]; 4],
+                    min: [0.0; 4],
+                    max: [0.0; 4],
+                    color: [0.0; 4],
+                }]),
+                usage: egui_wgpu::wgpu::BufferUsages::UNIFORM
+                    | egui_wgpu::wgpu::BufferUsages::COPY_DST,
+            });
+        let bind_group = device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[egui_wgpu::wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("highlight_bind_group"),
+        });
+
+        let shader = device.create_shader_module(egui_wgpu::wgpu::include_wgsl!("highlight.wgsl"));
+        let pipeline_layout =
+            device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+                label: Some("Highlight Pipeline Layout"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = device.create_render_pipeline(&egui_wgpu::wgpu::RenderPipelineDescriptor {
+            label: Some("Highlight Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: egui_wgpu::wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(egui_wgpu::wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(egui_wgpu::wgpu::ColorTargetState {
+                    format,
+                    blend: Some(egui_wgpu::wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: egui_wgpu::wgpu::PrimitiveState {
+                topology: egui_wgpu::wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: egui_wgpu::wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            color: [1.0, 0.5, 0.0, 1.0],
+            uniform_buffer,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    /// Draws `aabb`, placed by `model`, into `output` as seen through
+    /// `view_proj`.
+    pub fn render(
+        &self,
+        queue: &egui_wgpu::wgpu::Queue,
+        encoder: &mut egui_wgpu::wgpu::CommandEncoder,
+        output: &egui_wgpu::wgpu::TextureView,
+        view_proj: cgmath::Matrix4<f32>,
+        model: cgmath::Matrix4<f32>,
+        aabb: &bounds::Aabb,
+    ) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[HighlightUniform {
+                model_view_proj: (view_proj * model).into(),
+                min: aabb.min.to_homogeneous().into(),
+                max: aabb.max.to_homogeneous().into(),
+                color: self.color,
+            }]),
+        );
+
+        let mut pass = encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
+            label: Some("Highlight Pass"),
+            color_attachments: &[Some(egui_wgpu::wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Load,
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        // Two vertices for each of the 12 edges
+        pass.draw(0..24, 0..1);
+    }
+}
@@ -0,0 +1,35 @@
+// Outline of the selected instance's bounding box
+//
+// Drawn as 12 lines over the finished frame, without depth testing so that
+// it stays visible behind other instances.
+
+struct Highlight {
+    model_view_proj: mat4x4<f32>,
+    min: vec4<f32>,
+    max: vec4<f32>,
+    color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> highlight: Highlight;
+
+// Box corners with one bit per axis, in pairs along x, then y, then z
+const EDGES: array<u32, 24> = array<u32, 24>(
+    0u, 1u, 2u, 3u, 4u, 5u, 6u, 7u,
+    0u, 2u, 1u, 3u, 4u, 6u, 5u, 7u,
+    0u, 4u, 1u, 5u, 2u, 6u, 3u, 7u,
+);
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    var edges = EDGES;
+    let corner = edges[index];
+    let select_max = vec3<f32>(vec3<u32>(corner, corner >> 1u, corner >> 2u) & vec3<u32>(1u));
+    let position = mix(highlight.min.xyz, highlight.max.xyz, select_max);
+    return highlight.model_view_proj * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return highlight.color;
+}
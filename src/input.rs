@@ -0,0 +1,370 @@
+use crate::text_file;
+use anyhow::{bail, Context, Result};
+use egui_winit::winit::keyboard::KeyCode;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Where key bindings are kept, relative to the working directory.
+pub const BINDINGS_FILE: &str = "bindings.txt";
+
+/// Something a key can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    /// Held to move faster.
+    Sprint,
+    /// Held to change the movement speed by scrolling.
+    AdjustSpeed,
+    /// Axis aligned views, the opposite ones with Ctrl held.
+    FrontView,
+    RightView,
+    TopView,
+    ToggleProjection,
+    /// Hides and locks the cursor for mouse-look without holding a button.
+    GrabCursor,
+    Screenshot,
+    Exit,
+}
+
+impl Action {
+    pub const ALL: [Action; 15] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Sprint,
+        Action::AdjustSpeed,
+        Action::FrontView,
+        Action::RightView,
+        Action::TopView,
+        Action::ToggleProjection,
+        Action::GrabCursor,
+        Action::Screenshot,
+        Action::Exit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "Move Forward",
+            Action::MoveBackward => "Move Backward",
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::MoveUp => "Move Up",
+            Action::MoveDown => "Move Down",
+            Action::Sprint => "Sprint",
+            Action::AdjustSpeed => "Adjust Speed",
+            Action::FrontView => "Front View",
+            Action::RightView => "Right View",
+            Action::TopView => "Top View",
+            Action::ToggleProjection => "Toggle Projection",
+            Action::GrabCursor => "Grab Cursor",
+            Action::Screenshot => "Screenshot",
+            Action::Exit => "Exit",
+        }
+    }
+
+    fn default_keys(&self) -> &'static [KeyCode] {
+        match self {
+            Action::MoveForward => &[KeyCode::KeyW, KeyCode::ArrowUp],
+            Action::MoveBackward => &[KeyCode::KeyS, KeyCode::ArrowDown],
+            Action::MoveLeft => &[KeyCode::KeyA, KeyCode::ArrowLeft],
+            Action::MoveRight => &[KeyCode::KeyD, KeyCode::ArrowRight],
+            Action::MoveUp => &[KeyCode::Space],
+            Action::MoveDown => &[KeyCode::ShiftLeft],
+            Action::Sprint => &[KeyCode::ControlLeft],
+            Action::AdjustSpeed => &[KeyCode::AltLeft],
+            Action::FrontView => &[KeyCode::Numpad1],
+            Action::RightView => &[KeyCode::Numpad3],
+            Action::TopView => &[KeyCode::Numpad7],
+            Action::ToggleProjection => &[KeyCode::Numpad5],
+            Action::GrabCursor => &[KeyCode::KeyG],
+            Action::Screenshot => &[KeyCode::F12],
+            Action::Exit => &[KeyCode::Escape],
+        }
+    }
+}
+
+/// Which keys trigger which actions, any number of keys per action. Keys are
+/// physical, so bindings stay in place across keyboard layouts.
+///
+/// Saved as text with one action per line, its name followed by the names of
+/// its keys, as in `MoveForward KeyW ArrowUp`. Actions missing from the file
+/// keep their default keys.
+#[derive(Debug)]
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<KeyCode>>,
+    path: PathBuf,
+}
+
+impl InputMap {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        let mut input_map = Self {
+            bindings: HashMap::new(),
+            path: path.into(),
+        };
+        input_map.reset();
+        input_map
+    }
+
+    /// Reads the bindings at `path`, or starts from the defaults if there
+    /// is no such file.
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let mut input_map = Self::new(path);
+        if !input_map.path.exists() {
+            return Ok(input_map);
+        }
+
+        for (number, line) in text_file::read_lines(&input_map.path, "key bindings")? {
+            let mut names = line.split_whitespace();
+            let Some(action) = names.next().and_then(|name| {
+                Action::ALL
+                    .into_iter()
+                    .find(|action| format!("{:?}", action) == name)
+            }) else {
+                bail!("Unknown action on line {}", number);
+            };
+            let keys = names
+                .map(|name| {
+                    parse_key(name)
+                        .with_context(|| format!("Unknown key {} on line {}", name, number))
+                })
+                .collect::<Result<_>>()?;
+            input_map.bindings.insert(action, keys);
+        }
+        Ok(input_map)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut text = String::from("# Action followed by its keys\n");
+        for action in Action::ALL {
+            text += &format!("{:?}", action);
+            for key in self.keys(action) {
+                text += &format!(" {:?}", key);
+            }
+            text += "\n";
+        }
+        std::fs::write(&self.path, text)
+            .with_context(|| format!("Failed to write key bindings {}", self.path.display()))
+    }
+
+    /// Goes back to the default bindings.
+    pub fn reset(&mut self) {
+        self.bindings = Action::ALL
+            .into_iter()
+            .map(|action| (action, action.default_keys().to_vec()))
+            .collect();
+    }
+
+    /// The action bound to `key`, if any. With a key bound to several
+    /// actions, the first one in `Action::ALL` wins.
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|action| self.keys(*action).contains(&key))
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Binds `key` to `action`, replacing its key at `slot`, or adding it if
+    /// there is no such slot.
+    pub fn bind(&mut self, action: Action, slot: usize, key: KeyCode) {
+        let keys = self.bindings.entry(action).or_default();
+        match keys.get_mut(slot) {
+            Some(existing) => *existing = key,
+            None => keys.push(key),
+        }
+    }
+
+    pub fn unbind(&mut self, action: Action, slot: usize) {
+        if let Some(keys) = self.bindings.get_mut(&action) {
+            if slot < keys.len() {
+                keys.remove(slot);
+            }
+        }
+    }
+}
+
+/// Keys which can be bound, named as in `KeyCode`.
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::Backquote,
+    KeyCode::Backslash,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Comma,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::Equal,
+    KeyCode::IntlBackslash,
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Quote,
+    KeyCode::Semicolon,
+    KeyCode::Slash,
+    KeyCode::AltLeft,
+    KeyCode::AltRight,
+    KeyCode::Backspace,
+    KeyCode::CapsLock,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::Enter,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::Space,
+    KeyCode::Tab,
+    KeyCode::Delete,
+    KeyCode::End,
+    KeyCode::Home,
+    KeyCode::Insert,
+    KeyCode::PageDown,
+    KeyCode::PageUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::ArrowUp,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::NumpadAdd,
+    KeyCode::NumpadDecimal,
+    KeyCode::NumpadDivide,
+    KeyCode::NumpadEnter,
+    KeyCode::NumpadMultiply,
+    KeyCode::NumpadSubtract,
+    KeyCode::Escape,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::PrintScreen,
+    KeyCode::ScrollLock,
+    KeyCode::Pause,
+];
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .iter()
+        .copied()
+        .find(|key| format!("{:?}", key) == name)
+}
+
+/// Whether `key` can be bound, and so saved and loaded again.
+pub fn is_bindable(key: KeyCode) -> bool {
+    BINDABLE_KEYS.contains(&key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_file::temp_path;
+
+    #[test]
+    fn round_trips_through_file() {
+        let path = temp_path("bindings.txt");
+        let mut input_map = InputMap::new(&path);
+        input_map.bind(Action::MoveForward, 0, KeyCode::KeyI);
+        input_map.bind(Action::Screenshot, 1, KeyCode::PrintScreen);
+        input_map.unbind(Action::Exit, 0);
+        input_map.save().unwrap();
+
+        let loaded = InputMap::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        for action in Action::ALL {
+            assert_eq!(loaded.keys(action), input_map.keys(action), "{:?}", action);
+        }
+        assert_eq!(loaded.keys(Action::MoveForward)[0], KeyCode::KeyI);
+        assert!(loaded.keys(Action::Exit).is_empty());
+    }
+
+    #[test]
+    fn missing_actions_keep_defaults() {
+        let path = temp_path("partial-bindings.txt");
+        std::fs::write(&path, "# Comment\n\nSprint KeyZ\n").unwrap();
+        let loaded = InputMap::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.keys(Action::Sprint), [KeyCode::KeyZ]);
+        assert_eq!(loaded.keys(Action::MoveUp), [KeyCode::Space]);
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        for (name, text) in [
+            ("unknown-action.txt", "Teleport KeyT\n"),
+            ("unknown-key.txt", "Sprint KeyÄ\n"),
+        ] {
+            let path = temp_path(name);
+            std::fs::write(&path, text).unwrap();
+            let result = InputMap::load(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn bindable_keys_parse_by_name() {
+        for key in BINDABLE_KEYS {
+            assert_eq!(parse_key(&format!("{:?}", key)), Some(*key));
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use cgmath::prelude::*;
+use tracing::trace;
+
+pub struct Instance {
+    pub position: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+    /// Multiplied with the sampled texture color. An alpha below one makes
+    /// the instance transparent.
+    pub tint: [f32; 4],
+    /// Layer of the diffuse texture array to sample from.
+    pub layer: u32,
+    /// Free-form values passed through to the fragment shader.
+    pub custom: [f32; 4],
+    /// Strength of the Blinn-Phong highlight.
+    pub specular: f32,
+    /// Blinn-Phong exponent, higher values give smaller highlights.
+    pub shininess: f32,
+}
+
+impl Instance {
+    pub fn new(position: cgmath::Vector3<f32>, rotation: cgmath::Quaternion<f32>) -> Self {
+        Self {
+            position,
+            rotation,
+            tint: [1.0; 4],
+            layer: 0,
+            custom: [0.0; 4],
+            specular: 0.25,
+            shininess: 32.0,
+        }
+    }
+
+    pub fn model_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation)
+    }
+
+    /// Whether the tint's alpha lets the surfaces behind show through, which
+    /// draws the instance after the opaque ones with the blend mode.
+    pub fn is_transparent(&self) -> bool {
+        self.tint[3] < 1.0
+    }
+
+    pub fn to_raw(&self) -> InstanceRaw {
+        // Instances are never scaled, so the rotation alone is already the
+        // inverse transpose of the model matrix.
+        let normal = cgmath::Matrix3::from(self.rotation);
+        InstanceRaw {
+            model: self.model_matrix().into(),
+            normal: [
+                normal.x.extend(self.specular).into(),
+                normal.y.extend(self.shininess).into(),
+                normal.z.extend(0.0).into(),
+            ],
+            tint: self.tint,
+            custom: self.custom,
+            layer: self.layer,
+            lod_fade: 0.0,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Builds a cube of `per_row`^3 instances centered around the origin, each one
+/// tilted away from the center.
+pub fn grid(per_row: u32) -> Vec<Instance> {
+    let displacement = cgmath::Vector3::new(
+        per_row as f32 * 0.5,
+        per_row as f32 * 0.5,
+        per_row as f32 * 0.5,
+    );
+
+    (0..per_row)
+        .flat_map(|z| {
+            (0..per_row).flat_map(move |x| {
+                (0..per_row).map(move |y| {
+                    let position = cgmath::Vector3 {
+                        x: x as f32,
+                        y: y as f32,
+                        z: z as f32,
+                    } - displacement;
+
+                    let rotation = if position.is_zero() {
+                        cgmath::Quaternion::from_axis_angle(
+                            cgmath::Vector3::unit_z(),
+                            cgmath::Deg(0.0),
+                        )
+                    } else {
+                        cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(30.0))
+                    };
+
+                    Instance::new(position, rotation)
+                })
+            })
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Per-instance vertex data. The normal matrix columns are padded to 16 bytes
+/// so the same layout can also be read as a WGSL storage buffer struct. Their
+/// fourth components carry the specular strength and shininess, which keeps
+/// the vertex attributes within the 16 locations every adapter has.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    model: [[f32; 4]; 4],
+    normal: [[f32; 4]; 3],
+    tint: [f32; 4],
+    custom: [f32; 4],
+    layer: u32,
+    lod_fade: f32,
+    /// Rounds the size up to the 16 byte alignment of the WGSL struct.
+    _padding: [f32; 2],
+}
+
+impl InstanceRaw {
+    /// Progress of a level of detail cross-fade. Positive values fade the
+    /// instance out, negative ones fade it in, see `lod::LodMesh::select`.
+    pub fn with_lod_fade(mut self, lod_fade: f32) -> Self {
+        self.lod_fade = lod_fade;
+        self
+    }
+
+    pub fn desc() -> egui_wgpu::wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        egui_wgpu::wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<InstanceRaw>() as egui_wgpu::wgpu::BufferAddress,
+            step_mode: egui_wgpu::wgpu::VertexStepMode::Instance,
+            attributes: &[
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 5,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 20]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 24]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 28]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 32]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 36]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 14,
+                    format: egui_wgpu::wgpu::VertexFormat::Uint32,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 37]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 15,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+/// GPU side copy of the instance list. The buffer only ever grows, so removing
+/// instances just re-uploads the remaining ones and draws fewer of them.
+pub struct InstanceBuffer {
+    pub buffer: egui_wgpu::wgpu::Buffer,
+    capacity: usize,
+    len: usize,
+}
+
+impl InstanceBuffer {
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        queue: &egui_wgpu::wgpu::Queue,
+        instances: &[Instance],
+    ) -> Self {
+        let capacity = instances.len().max(1).next_power_of_two();
+        let mut instance_buffer = Self {
+            buffer: Self::create_buffer(device, capacity),
+            capacity,
+            len: 0,
+        };
+        instance_buffer.update(device, queue, instances);
+        instance_buffer
+    }
+
+    fn create_buffer(device: &egui_wgpu::wgpu::Device, capacity: usize) -> egui_wgpu::wgpu::Buffer {
+        device.create_buffer(&egui_wgpu::wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (capacity * std::mem::size_of::<InstanceRaw>()) as egui_wgpu::wgpu::BufferAddress,
+            // Also bound as a storage buffer by the GPU culling pass
+            usage: egui_wgpu::wgpu::BufferUsages::VERTEX
+                | egui_wgpu::wgpu::BufferUsages::COPY_DST
+                | egui_wgpu::wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Uploads `instances`, reallocating the buffer if it no longer fits.
+    pub fn update(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        queue: &egui_wgpu::wgpu::Queue,
+        instances: &[Instance],
+    ) {
+        let data = instances
+            .iter()
+            .map(Instance::to_raw)
+            .collect::<Vec<InstanceRaw>>();
+        self.update_raw(device, queue, &data);
+    }
+
+    pub fn update_raw(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        queue: &egui_wgpu::wgpu::Queue,
+        data: &[InstanceRaw],
+    ) {
+        if data.len() > self.capacity {
+            self.capacity = data.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+            trace!("Instance buffer grown to {} instances", self.capacity);
+        }
+
+        self.len = data.len();
+        if self.len > 0 {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(data));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
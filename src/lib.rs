@@ -1,15 +1,35 @@
 use egui_winit::winit::{
-    event::*,
-    event_loop::EventLoop,
-    keyboard::{KeyCode, PhysicalKey},
-    window::WindowBuilder,
+    event::*, event_loop::EventLoop, keyboard::PhysicalKey, window::WindowBuilder,
 };
 use tracing::{debug, error, info, info_span, trace, warn};
 
+pub mod bookmark;
+pub mod bounds;
 pub mod camera;
+pub mod camera_path;
+pub mod cluster;
+pub mod culling;
+pub mod deferred;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
 pub mod gui;
+pub mod hdr;
+pub mod highlight;
+pub mod input;
+pub mod instance;
+pub mod light;
+pub mod lod;
+pub mod mesh;
+pub mod postprocess;
+pub mod sampling;
+pub mod screenshot;
+pub mod settings;
+pub mod shadow;
+pub mod ssao;
 pub mod state;
+pub mod text_file;
 pub mod texture;
+pub mod transparency;
 
 use state::State;
 
@@ -63,68 +83,70 @@ pub async fn run() {
         Event::DeviceEvent {
             event: DeviceEvent::MouseMotion{ delta, },
             .. // We're not using device_id currently
-        } => if state.mouse_pressed {
-            state.camera_controller.process_mouse(delta.0, delta.1)
+        } => {
+            state.process_mouse(delta.0, delta.1)
         }
         Event::WindowEvent {
             ref event,
             window_id,
         } if window_id == state.window().id() && !state.input(event) => {
-            if !state.input(event) {
-                match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        event:
-                            KeyEvent {
-                                state: ElementState::Pressed,
-                                physical_key: PhysicalKey::Code(KeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } => control_flow.exit(),
+            match event {
+                WindowEvent::CloseRequested => control_flow.exit(),
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            state: ElementState::Pressed,
+                            physical_key: PhysicalKey::Code(key),
+                            ..
+                        },
+                    ..
+                } if !state.egui.wants_keyboard_input()
+                    && state.action(*key) == Some(input::Action::Exit) =>
+                {
+                    control_flow.exit()
+                }
 
-                    WindowEvent::Resized(physical_size) => {
-                        surface_configured = true;
-                        state.resize(*physical_size);
-                    }
+                WindowEvent::Resized(physical_size) => {
+                    surface_configured = true;
+                    state.resize(*physical_size);
+                }
 
-                    WindowEvent::RedrawRequested => {
-                        state.window().request_redraw();
-                        if !surface_configured {
-                            return;
-                        }
+                WindowEvent::RedrawRequested => {
+                    state.window().request_redraw();
+                    if !surface_configured {
+                        return;
+                    }
 
-                        let now = std::time::Instant::now();
-                        let dt = now - last_render_time;
-                        last_render_time = now;
-                        state.status.delta = dt.as_micros();
-                        state.status.fps = 1_000_000.0 / dt.as_micros() as f32;
-                        state.status.fps_avg =
-                            0.95 * state.status.fps_avg + 0.05 * state.status.fps;
-                        state.update(dt);
-                        match state.render() {
-                            Ok(_) => {}
+                    let now = std::time::Instant::now();
+                    let dt = now - last_render_time;
+                    last_render_time = now;
+                    state.status.delta = dt.as_micros();
+                    state.status.fps = 1_000_000.0 / dt.as_micros() as f32;
+                    state.status.fps_avg =
+                        0.95 * state.status.fps_avg + 0.05 * state.status.fps;
+                    state.update(dt);
+                    match state.render() {
+                        Ok(_) => {}
 
-                            Err(
-                                egui_wgpu::wgpu::SurfaceError::Lost
-                                | egui_wgpu::wgpu::SurfaceError::Outdated,
-                            ) => state.resize(state.size),
+                        Err(
+                            egui_wgpu::wgpu::SurfaceError::Lost
+                            | egui_wgpu::wgpu::SurfaceError::Outdated,
+                        ) => state.resize(state.size),
 
-                            Err(egui_wgpu::wgpu::SurfaceError::OutOfMemory) => {
-                                error!("OutOfMemory");
-                                control_flow.exit();
-                            }
+                        Err(egui_wgpu::wgpu::SurfaceError::OutOfMemory) => {
+                            error!("OutOfMemory");
+                            control_flow.exit();
+                        }
 
-                            Err(egui_wgpu::wgpu::SurfaceError::Timeout) => {
-                                warn!("Surface timeout")
-                            }
+                        Err(egui_wgpu::wgpu::SurfaceError::Timeout) => {
+                            warn!("Surface timeout")
                         }
                     }
-
-                    _ => {}
                 }
+
+                _ => {}
             }
-            state.egui.handle_input(&mut state.window, event);
+            state.egui.handle_input(state.window, event);
         }
         _ => {}
     });
@@ -0,0 +1,284 @@
+use crate::{sampling, shadow};
+use cgmath::prelude::*;
+use egui_wgpu::wgpu::util::DeviceExt;
+use tracing::trace;
+
+pub struct PointLight {
+    pub position: cgmath::Point3<f32>,
+    /// Linear color, multiplied by `intensity`.
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Distance at which the light has faded out completely.
+    pub range: f32,
+}
+
+impl PointLight {
+    fn to_raw(&self) -> PointLightRaw {
+        PointLightRaw {
+            position: self.position.into(),
+            range: self.range,
+            color: self.color.map(|c| c * self.intensity),
+            _padding: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PointLightRaw {
+    position: [f32; 3],
+    range: f32,
+    color: [f32; 3],
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightParams {
+    sun_direction: [f32; 3],
+    light_count: u32,
+    sun_color: [f32; 3],
+    _padding: u32,
+    ambient: [f32; 3],
+    _padding2: u32,
+}
+
+/// `count` point lights spread through a cube of size `extent` around the
+/// origin, cycling through hues.
+pub fn scatter(count: usize, extent: f32) -> Vec<PointLight> {
+    // Halton sequences fill the cube evenly without clumping
+    (0..count)
+        .map(|i| {
+            let position = cgmath::Point3::new(
+                sampling::halton(i + 1, 2) - 0.5,
+                sampling::halton(i + 1, 3) - 0.5,
+                sampling::halton(i + 1, 5) - 0.5,
+            ) * extent;
+            let hue = i as f32 * 0.618034 % 1.0;
+            let channel = |offset: f32| {
+                let x = ((hue + offset) % 1.0 * 6.0 - 3.0).abs() - 1.0;
+                x.clamp(0.0, 1.0)
+            };
+            PointLight {
+                position,
+                color: [channel(0.0), channel(2.0 / 3.0), channel(1.0 / 3.0)],
+                intensity: 4.0,
+                range: 4.0,
+            }
+        })
+        .collect()
+}
+
+/// The scene's lights along with the GPU buffers shaders read them from. A
+/// directional sun and ambient term light everything, point lights are
+/// stored in a storage buffer that grows as needed. Only the sun casts
+/// shadows.
+pub struct Lights {
+    pub point_lights: Vec<PointLight>,
+    /// Direction the sunlight travels in.
+    pub sun_direction: cgmath::Vector3<f32>,
+    pub sun_color: [f32; 3],
+    pub ambient: [f32; 3],
+    pub shadow: shadow::ShadowMap,
+    pub bind_group_layout: egui_wgpu::wgpu::BindGroupLayout,
+    pub bind_group: egui_wgpu::wgpu::BindGroup,
+    params_buffer: egui_wgpu::wgpu::Buffer,
+    light_buffer: egui_wgpu::wgpu::Buffer,
+    capacity: usize,
+}
+
+impl Lights {
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        point_lights: Vec<PointLight>,
+        shadow: shadow::ShadowMap,
+    ) -> Self {
+        let bind_group_layout =
+            device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    egui_wgpu::wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT
+                            | egui_wgpu::wgpu::ShaderStages::COMPUTE,
+                        ty: egui_wgpu::wgpu::BindingType::Buffer {
+                            ty: egui_wgpu::wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    egui_wgpu::wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT
+                            | egui_wgpu::wgpu::ShaderStages::COMPUTE,
+                        ty: egui_wgpu::wgpu::BindingType::Buffer {
+                            ty: egui_wgpu::wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    egui_wgpu::wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+                        ty: egui_wgpu::wgpu::BindingType::Buffer {
+                            ty: egui_wgpu::wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    egui_wgpu::wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+                        ty: egui_wgpu::wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: egui_wgpu::wgpu::TextureViewDimension::D2,
+                            sample_type: egui_wgpu::wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    egui_wgpu::wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+                        ty: egui_wgpu::wgpu::BindingType::Sampler(
+                            egui_wgpu::wgpu::SamplerBindingType::Comparison,
+                        ),
+                        count: None,
+                    },
+                ],
+                label: Some("light_bind_group_layout"),
+            });
+
+        let params_buffer =
+            device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
+                label: Some("Light Params Buffer"),
+                contents: bytemuck::cast_slice(&[<LightParams as bytemuck::Zeroable>::zeroed()]),
+                usage: egui_wgpu::wgpu::BufferUsages::UNIFORM
+                    | egui_wgpu::wgpu::BufferUsages::COPY_DST,
+            });
+        let capacity = point_lights.len().max(1).next_power_of_two();
+        let light_buffer = Self::create_light_buffer(device, capacity);
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &params_buffer,
+            &light_buffer,
+            &shadow,
+        );
+
+        Self {
+            point_lights,
+            sun_direction: cgmath::Vector3::new(-0.3, -1.0, -0.5).normalize(),
+            sun_color: [0.6, 0.55, 0.5],
+            ambient: [0.08, 0.09, 0.12],
+            shadow,
+            bind_group_layout,
+            bind_group,
+            params_buffer,
+            light_buffer,
+            capacity,
+        }
+    }
+
+    fn create_light_buffer(
+        device: &egui_wgpu::wgpu::Device,
+        capacity: usize,
+    ) -> egui_wgpu::wgpu::Buffer {
+        device.create_buffer(&egui_wgpu::wgpu::BufferDescriptor {
+            label: Some("Point Light Buffer"),
+            size: (capacity * std::mem::size_of::<PointLightRaw>())
+                as egui_wgpu::wgpu::BufferAddress,
+            usage: egui_wgpu::wgpu::BufferUsages::STORAGE | egui_wgpu::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        device: &egui_wgpu::wgpu::Device,
+        layout: &egui_wgpu::wgpu::BindGroupLayout,
+        params_buffer: &egui_wgpu::wgpu::Buffer,
+        light_buffer: &egui_wgpu::wgpu::Buffer,
+        shadow: &shadow::ShadowMap,
+    ) -> egui_wgpu::wgpu::BindGroup {
+        device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: shadow.params_buffer.as_entire_binding(),
+                },
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: egui_wgpu::wgpu::BindingResource::TextureView(&shadow.texture.view),
+                },
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: egui_wgpu::wgpu::BindingResource::Sampler(&shadow.texture.sampler),
+                },
+            ],
+            label: Some("light_bind_group"),
+        })
+    }
+
+    /// Uploads the current lights, reallocating the buffer if they no longer
+    /// fit. Cheap enough to call every frame.
+    pub fn update(&mut self, device: &egui_wgpu::wgpu::Device, queue: &egui_wgpu::wgpu::Queue) {
+        if self.point_lights.len() > self.capacity {
+            self.capacity = self.point_lights.len().next_power_of_two();
+            self.light_buffer = Self::create_light_buffer(device, self.capacity);
+            self.bind_group = Self::create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.params_buffer,
+                &self.light_buffer,
+                &self.shadow,
+            );
+            trace!("Light buffer grown to {} lights", self.capacity);
+        }
+
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[LightParams {
+                sun_direction: self.sun_direction.normalize().into(),
+                light_count: self.point_lights.len() as u32,
+                sun_color: self.sun_color,
+                _padding: 0,
+                ambient: self.ambient,
+                _padding2: 0,
+            }]),
+        );
+        if !self.point_lights.is_empty() {
+            let data = self
+                .point_lights
+                .iter()
+                .map(PointLight::to_raw)
+                .collect::<Vec<_>>();
+            queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&data));
+        }
+    }
+}
+
+/// Compiles `source` with lighting.wgsl prepended, which declares the camera
+/// at group 1, the lights at group 2 and a `shade` function using both.
+pub fn create_shader_module(
+    device: &egui_wgpu::wgpu::Device,
+    label: &str,
+    source: &str,
+) -> egui_wgpu::wgpu::ShaderModule {
+    device.create_shader_module(egui_wgpu::wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: egui_wgpu::wgpu::ShaderSource::Wgsl(
+            format!("{}\n{}", include_str!("lighting.wgsl"), source).into(),
+        ),
+    })
+}
@@ -0,0 +1,118 @@
+// Scene bindings and lighting shared by the forward and deferred shaders,
+// prepended to both by `light::create_shader_module`.
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+struct PointLight {
+    position: vec3<f32>,
+    range: f32,
+    // Already multiplied by the intensity
+    color: vec3<f32>,
+}
+
+struct LightParams {
+    sun_direction: vec3<f32>,
+    light_count: u32,
+    sun_color: vec3<f32>,
+    ambient: vec3<f32>,
+}
+
+@group(2) @binding(0)
+var<uniform> light_params: LightParams;
+@group(2) @binding(1)
+var<storage, read> point_lights: array<PointLight>;
+
+struct ShadowParams {
+    light_view_proj: mat4x4<f32>,
+    bias: f32,
+    enabled: u32,
+    texel_size: f32,
+}
+
+@group(2) @binding(2)
+var<uniform> shadow: ShadowParams;
+@group(2) @binding(3)
+var t_shadow: texture_depth_2d;
+@group(2) @binding(4)
+var s_shadow: sampler_comparison;
+
+// Shininess is stored normalized to this in the G-buffer
+const MAX_SHININESS: f32 = 256.0;
+
+struct Surface {
+    position: vec3<f32>,
+    normal: vec3<f32>,
+    albedo: vec3<f32>,
+    specular: f32,
+    shininess: f32,
+}
+
+// Blinn-Phong
+fn light_contribution(surface: Surface, view_dir: vec3<f32>, light_dir: vec3<f32>, radiance: vec3<f32>) -> vec3<f32> {
+    let n_dot_l = dot(surface.normal, light_dir);
+    if n_dot_l <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+    let half_dir = normalize(light_dir + view_dir);
+    let specular = pow(max(dot(surface.normal, half_dir), 0.0), surface.shininess) * surface.specular;
+    return (surface.albedo * n_dot_l + specular) * radiance;
+}
+
+fn point_light_contribution(surface: Surface, view_dir: vec3<f32>, light: PointLight) -> vec3<f32> {
+    let to_light = light.position - surface.position;
+    let distance = length(to_light);
+    if distance >= light.range {
+        return vec3<f32>(0.0);
+    }
+    // Inverse square falloff, windowed to reach zero at the range
+    let ratio = distance / light.range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    let attenuation = window * window / (distance * distance + 1.0);
+    return light_contribution(surface, view_dir, to_light / distance, light.color * attenuation);
+}
+
+// Fraction of the sunlight reaching `position`, filtered over 3x3 texels of
+// the shadow map. Everything outside of it is lit.
+fn sun_visibility(position: vec3<f32>) -> f32 {
+    if shadow.enabled == 0u {
+        return 1.0;
+    }
+    let clip = shadow.light_view_proj * vec4<f32>(position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    if any(abs(ndc.xy) > vec2<f32>(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    var visibility = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+            visibility += textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, ndc.z - shadow.bias);
+        }
+    }
+    return visibility / 9.0;
+}
+
+// Ambient and sunlight, without the point lights
+fn shade_directional(surface: Surface, view_dir: vec3<f32>) -> vec3<f32> {
+    let ambient = surface.albedo * light_params.ambient;
+    let sun = light_params.sun_color * sun_visibility(surface.position);
+    return ambient + light_contribution(surface, view_dir, -light_params.sun_direction, sun);
+}
+
+// Lit by every point light, see `shade_clustered` in shader.wgsl for the
+// forward path
+fn shade(surface: Surface) -> vec3<f32> {
+    let view_dir = normalize(camera.position.xyz - surface.position);
+    var color = shade_directional(surface, view_dir);
+    for (var i = 0u; i < light_params.light_count; i++) {
+        color += point_light_contribution(surface, view_dir, point_lights[i]);
+    }
+    return color;
+}
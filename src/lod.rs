@@ -0,0 +1,129 @@
+use crate::{camera, mesh};
+
+/// The most levels a `LodMesh` can have, bounded by the GPU culling pass which
+/// keeps one indirect draw per level.
+pub const MAX_LOD_LEVELS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LodSelection {
+    /// Switch levels at fixed distances from the camera.
+    Distance,
+    /// Switch levels once the bounding sphere covers less than a fraction of
+    /// the screen height, so the field of view is taken into account.
+    ScreenSize,
+}
+
+pub struct LodLevel {
+    pub mesh: mesh::Mesh,
+    /// Distance past which the next level is used.
+    pub distance: f32,
+    /// Fraction of the screen height below which the next level is used.
+    pub screen_size: f32,
+}
+
+/// A mesh with progressively simpler versions of itself, ordered from the most
+/// to the least detailed. The thresholds of the last level are never used.
+pub struct LodMesh {
+    pub levels: Vec<LodLevel>,
+    pub selection: LodSelection,
+    pub enabled: bool,
+    /// Width of the cross-fade between two levels, as a fraction of the
+    /// switch distance. Zero disables dithering and switches instantly.
+    pub fade_band: f32,
+}
+
+impl LodMesh {
+    pub fn new(levels: Vec<LodLevel>) -> Self {
+        assert!(
+            !levels.is_empty() && levels.len() <= MAX_LOD_LEVELS,
+            "A LodMesh needs between 1 and {} levels",
+            MAX_LOD_LEVELS
+        );
+        Self {
+            levels,
+            selection: LodSelection::Distance,
+            enabled: true,
+            fade_band: 0.1,
+        }
+    }
+
+    /// Builds the first level from the given geometry and one simplified level
+    /// per entry in `cells`, see `mesh::simplify`. Thresholds start out evenly
+    /// spaced and are meant to be tuned afterwards.
+    pub fn generate(
+        device: &egui_wgpu::wgpu::Device,
+        label: &str,
+        vertices: &[mesh::Vertex],
+        indices: &[u16],
+        cells: &[u32],
+    ) -> Self {
+        let base = mesh::Mesh::new(device, label, vertices, indices);
+        let level = |i: usize, mesh| LodLevel {
+            mesh,
+            distance: 15.0 * (i + 1) as f32,
+            screen_size: 0.11 / (i + 1) as f32,
+        };
+
+        let mut levels = vec![level(0, base)];
+        for (i, cells) in cells.iter().enumerate() {
+            let (vertices, indices) = mesh::simplify(vertices, indices, *cells);
+            let mesh = mesh::Mesh::new(
+                device,
+                &format!("{} LOD{}", label, i + 1),
+                &vertices,
+                &indices,
+            );
+            levels.push(level(i + 1, mesh));
+        }
+
+        Self::new(levels)
+    }
+
+    /// Number of levels in use, which is just the base level while disabled.
+    pub fn active_levels(&self) -> usize {
+        if self.enabled {
+            self.levels.len()
+        } else {
+            1
+        }
+    }
+
+    /// The most detailed level, whose bounds are used for culling.
+    pub fn base(&self) -> &mesh::Mesh {
+        &self.levels[0].mesh
+    }
+
+    /// Camera distances at which each level hands over to the next one. Screen
+    /// size thresholds are converted using the bounding sphere of the base
+    /// mesh, which makes both selection modes a plain distance comparison.
+    pub fn switch_distances(&self, projection: &dyn camera::Projection) -> [f32; MAX_LOD_LEVELS] {
+        let mut distances = [f32::INFINITY; MAX_LOD_LEVELS];
+        for (i, level) in self.levels.iter().enumerate().take(self.levels.len() - 1) {
+            distances[i] = match self.selection {
+                LodSelection::Distance => level.distance,
+                LodSelection::ScreenSize => projection.distance_for_half_height(
+                    self.base().sphere.radius / level.screen_size.max(f32::EPSILON),
+                ),
+            };
+        }
+        distances
+    }
+
+    /// Picks the level for an object at `distance` from the camera. The second
+    /// value is how far the object is into the cross-fade towards the next
+    /// level, or zero if it isn't fading.
+    pub fn select(&self, distances: &[f32; MAX_LOD_LEVELS], distance: f32) -> (usize, f32) {
+        let last = self.active_levels() - 1;
+        let level = distances[..last]
+            .iter()
+            .position(|switch| distance <= *switch)
+            .unwrap_or(last);
+        if level == last || self.fade_band <= 0.0 {
+            return (level, 0.0);
+        }
+
+        let band = distances[level] * self.fade_band;
+        let fade = (distance - (distances[level] - band)) / band;
+        (level, fade.max(0.0))
+    }
+}
@@ -0,0 +1,204 @@
+use crate::bounds;
+use cgmath::prelude::*;
+use egui_wgpu::wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+}
+
+impl Vertex {
+    pub fn desc() -> egui_wgpu::wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        egui_wgpu::wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Vertex>() as egui_wgpu::wgpu::BufferAddress,
+            step_mode: egui_wgpu::wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x3,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x2,
+                },
+                egui_wgpu::wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 5]>() as egui_wgpu::wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: egui_wgpu::wgpu::VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+pub struct Mesh {
+    pub vertex_buffer: egui_wgpu::wgpu::Buffer,
+    pub index_buffer: egui_wgpu::wgpu::Buffer,
+    pub num_indices: u32,
+    pub aabb: bounds::Aabb,
+    pub sphere: bounds::BoundingSphere,
+}
+
+impl Mesh {
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        label: &str,
+        vertices: &[Vertex],
+        indices: &[u16],
+    ) -> Self {
+        let vertex_buffer =
+            device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Vertex Buffer", label)),
+                contents: bytemuck::cast_slice(vertices),
+                usage: egui_wgpu::wgpu::BufferUsages::VERTEX,
+            });
+        let index_buffer =
+            device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Index Buffer", label)),
+                contents: bytemuck::cast_slice(indices),
+                usage: egui_wgpu::wgpu::BufferUsages::INDEX,
+            });
+
+        let points = || vertices.iter().map(|v| cgmath::Point3::from(v.position));
+        let aabb = bounds::Aabb::from_points(points());
+        let sphere = bounds::BoundingSphere::from_points(&aabb, points());
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+            aabb,
+            sphere,
+        }
+    }
+
+    /// Whether the mesh placed with `model` is at least partially inside
+    /// `frustum`. The sphere test rejects most instances cheaply, the box test
+    /// catches the ones the sphere is too loose for.
+    pub fn is_visible(
+        &self,
+        frustum: &crate::camera::Frustum,
+        model: &cgmath::Matrix4<f32>,
+    ) -> bool {
+        frustum.contains_sphere(&self.sphere.transform(model))
+            && frustum.intersects_aabb(&self.aabb.transform(model))
+    }
+}
+
+/// A flat quad in the XY plane facing +Z, split into `subdivisions` by
+/// `subdivisions` cells.
+pub fn quad(subdivisions: u16) -> (Vec<Vertex>, Vec<u16>) {
+    let n = subdivisions.max(1);
+    let step = 1.0 / n as f32;
+
+    let vertices = (0..=n)
+        .flat_map(|row| {
+            (0..=n).map(move |col| Vertex {
+                position: [-0.5 + col as f32 * step, 0.5 - row as f32 * step, 0.0],
+                tex_coords: [col as f32 * step, row as f32 * step],
+                normal: [0.0, 0.0, 1.0],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let indices = (0..n)
+        .flat_map(|row| {
+            (0..n).flat_map(move |col| {
+                let top_left = row * (n + 1) + col;
+                let top_right = top_left + 1;
+                let bottom_left = top_left + n + 1;
+                let bottom_right = bottom_left + 1;
+                [
+                    top_right,
+                    top_left,
+                    bottom_left,
+                    top_right,
+                    bottom_left,
+                    bottom_right,
+                ]
+            })
+        })
+        .collect::<Vec<_>>();
+
+    (vertices, indices)
+}
+
+/// Vertex clustering simplification. The bounding box is split into `cells`
+/// cells per axis and every vertex in a cell is merged into the one furthest
+/// from the center, which keeps the silhouette intact. Triangles that collapse
+/// are dropped.
+pub fn simplify(vertices: &[Vertex], indices: &[u16], cells: u32) -> (Vec<Vertex>, Vec<u16>) {
+    let cells = cells.max(1);
+    let aabb = bounds::Aabb::from_points(vertices.iter().map(|v| cgmath::Point3::from(v.position)));
+    let center = aabb.center();
+    let extent = aabb.max - aabb.min;
+
+    let cell_of = |position: [f32; 3]| {
+        let axis = |i: usize| {
+            if extent[i] > 0.0 {
+                let cell = ((position[i] - aabb.min[i]) / extent[i] * cells as f32) as u32;
+                cell.min(cells - 1)
+            } else {
+                0
+            }
+        };
+        (axis(0), axis(1), axis(2))
+    };
+    let distance = |v: &Vertex| cgmath::Point3::from(v.position).distance2(center);
+
+    // Cell -> index of the representative vertex in `vertices`
+    let mut representatives = std::collections::HashMap::new();
+    for (i, vertex) in vertices.iter().enumerate() {
+        representatives
+            .entry(cell_of(vertex.position))
+            .and_modify(|rep: &mut usize| {
+                if distance(vertex) > distance(&vertices[*rep]) {
+                    *rep = i;
+                }
+            })
+            .or_insert(i);
+    }
+
+    let mut remap = std::collections::HashMap::new();
+    let mut new_vertices = Vec::new();
+    let mut new_index = |i: u16| {
+        let rep = representatives[&cell_of(vertices[i as usize].position)];
+        *remap.entry(rep).or_insert_with(|| {
+            new_vertices.push(vertices[rep]);
+            (new_vertices.len() - 1) as u16
+        })
+    };
+
+    let mut seen = std::collections::HashSet::new();
+    let mut new_indices = Vec::new();
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [
+            new_index(triangle[0]),
+            new_index(triangle[1]),
+            new_index(triangle[2]),
+        ];
+        if a == b || b == c || a == c {
+            continue;
+        }
+        // Rotate so the smallest index comes first, which keeps the winding
+        // while letting duplicates compare equal.
+        let key = if a < b && a < c {
+            [a, b, c]
+        } else if b < c {
+            [b, c, a]
+        } else {
+            [c, a, b]
+        };
+        if seen.insert(key) {
+            new_indices.extend_from_slice(&[a, b, c]);
+        }
+    }
+
+    (new_vertices, new_indices)
+}
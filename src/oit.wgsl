@@ -0,0 +1,36 @@
+// Weighted blended order-independent transparency composite
+//
+// `t_accum` holds the sum of the weighted premultiplied colors and their
+// weighted alphas, `t_revealage` the product of one minus every alpha,
+// i.e. how much of the background is still visible.
+
+@group(0) @binding(0)
+var t_accum: texture_2d<f32>;
+@group(0) @binding(1)
+var t_revealage: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// A single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+// Blended over the opaque scene with the alpha being one minus revealage
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.clip_position.xy);
+    let revealage = textureLoad(t_revealage, coord, 0).r;
+    if revealage >= 1.0 {
+        discard;
+    }
+    let accum = textureLoad(t_accum, coord, 0);
+    let color = accum.rgb / clamp(accum.a, 1e-4, 5e4);
+    return vec4<f32>(color, 1.0 - revealage);
+}
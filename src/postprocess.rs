@@ -0,0 +1,717 @@
+use crate::{hdr, texture};
+use egui_wgpu::wgpu::util::DeviceExt;
+
+/// Entries per axis of the color grading LUT.
+const LUT_SIZE: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EffectSettings {
+    /// Parts of the HDR scene brighter than `threshold` bleed into their
+    /// surroundings. Blurred at half resolution.
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        radius: f32,
+    },
+    /// Fast approximate anti-aliasing, a cheaper alternative to MSAA that also
+    /// smooths aliasing inside of textures. `edge_threshold` is the local
+    /// contrast, relative to the brightest pixel, needed to count as an edge,
+    /// and `subpixel` how much single pixel details get blurred.
+    Fxaa {
+        subpixel: f32,
+        edge_threshold: f32,
+        edge_threshold_min: f32,
+    },
+    /// Darkens the image towards the corners, starting at `radius` where 1 is
+    /// the distance from the center to a corner.
+    Vignette {
+        intensity: f32,
+        radius: f32,
+        smoothness: f32,
+    },
+    /// Splits red and blue apart towards the edges, `strength` is the offset
+    /// in pixels at the edge of the screen.
+    ChromaticAberration { strength: f32 },
+    /// White balance, saturation and contrast baked into a 3D LUT.
+    ColorGrading {
+        intensity: f32,
+        contrast: f32,
+        saturation: f32,
+        temperature: f32,
+        tint: f32,
+    },
+    /// Per pixel noise that changes every frame, strongest in the mid tones.
+    FilmGrain { intensity: f32 },
+}
+
+impl EffectSettings {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bloom { .. } => "Bloom",
+            Self::Fxaa { .. } => "FXAA",
+            Self::Vignette { .. } => "Vignette",
+            Self::ChromaticAberration { .. } => "Chromatic aberration",
+            Self::ColorGrading { .. } => "Color grading",
+            Self::FilmGrain { .. } => "Film grain",
+        }
+    }
+
+    /// Whether the effect works on the HDR scene. These always run before
+    /// tonemapping, regardless of where they are in the chain.
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, Self::Bloom { .. })
+    }
+
+    fn params(&self) -> [f32; 4] {
+        match *self {
+            Self::Bloom {
+                threshold,
+                intensity,
+                radius,
+            } => [threshold, intensity, radius, 0.0],
+            Self::Fxaa {
+                subpixel,
+                edge_threshold,
+                edge_threshold_min,
+            } => [subpixel, edge_threshold, edge_threshold_min, 0.0],
+            Self::Vignette {
+                intensity,
+                radius,
+                smoothness,
+            } => [intensity, radius, smoothness, 0.0],
+            Self::ChromaticAberration { strength } => [strength, 0.0, 0.0, 0.0],
+            Self::ColorGrading { intensity, .. } => [intensity, 0.0, 0.0, 0.0],
+            Self::FilmGrain { intensity } => [intensity, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct EffectUniform {
+    params: [f32; 4],
+    texel_size: [f32; 2],
+    frame: u32,
+    _padding: u32,
+}
+
+pub struct Effect {
+    pub enabled: bool,
+    pub settings: EffectSettings,
+    uniform_buffer: egui_wgpu::wgpu::Buffer,
+    bind_group: egui_wgpu::wgpu::BindGroup,
+}
+
+/// A render target along with the bind group reading it as an effect input.
+struct Target {
+    texture: texture::Texture,
+    bind_group: egui_wgpu::wgpu::BindGroup,
+}
+
+/// Ordered chain of full screen effects around the tonemapping pass. HDR
+/// effects are applied to the scene in place, the others ping-pong between
+/// two surface sized targets with the last one writing to the output.
+pub struct PostProcess {
+    effects: Vec<Effect>,
+    input_layout: egui_wgpu::wgpu::BindGroupLayout,
+    scene_input: egui_wgpu::wgpu::BindGroup,
+    bloom_targets: [Target; 2],
+    ldr_targets: [Target; 2],
+    format: egui_wgpu::wgpu::TextureFormat,
+    bright_pass: egui_wgpu::wgpu::RenderPipeline,
+    blur_horizontal: egui_wgpu::wgpu::RenderPipeline,
+    blur_vertical: egui_wgpu::wgpu::RenderPipeline,
+    bloom_composite: egui_wgpu::wgpu::RenderPipeline,
+    fxaa: egui_wgpu::wgpu::RenderPipeline,
+    vignette: egui_wgpu::wgpu::RenderPipeline,
+    chromatic_aberration: egui_wgpu::wgpu::RenderPipeline,
+    color_grading: egui_wgpu::wgpu::RenderPipeline,
+    film_grain: egui_wgpu::wgpu::RenderPipeline,
+    lut: texture::Texture,
+    lut_bind_group: egui_wgpu::wgpu::BindGroup,
+    /// Grade the LUT was last baked with.
+    lut_grade: Option<[f32; 4]>,
+    frame: u32,
+}
+
+impl PostProcess {
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        config: &egui_wgpu::wgpu::SurfaceConfiguration,
+        hdr: &hdr::HdrPipeline,
+    ) -> Self {
+        let fragment_entry =
+            |binding, ty: egui_wgpu::wgpu::BindingType| egui_wgpu::wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+                ty,
+                count: None,
+            };
+        let texture_layout = |dimension, label| {
+            device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    fragment_entry(
+                        0,
+                        egui_wgpu::wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: dimension,
+                            sample_type: egui_wgpu::wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                    ),
+                    fragment_entry(
+                        1,
+                        egui_wgpu::wgpu::BindingType::Sampler(
+                            egui_wgpu::wgpu::SamplerBindingType::Filtering,
+                        ),
+                    ),
+                ],
+                label: Some(label),
+            })
+        };
+        let input_layout = texture_layout(
+            egui_wgpu::wgpu::TextureViewDimension::D2,
+            "post_process_input_bind_group_layout",
+        );
+        let lut_layout = texture_layout(
+            egui_wgpu::wgpu::TextureViewDimension::D3,
+            "post_process_lut_bind_group_layout",
+        );
+        let effect_layout =
+            device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+                entries: &[fragment_entry(
+                    0,
+                    egui_wgpu::wgpu::BindingType::Buffer {
+                        ty: egui_wgpu::wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                )],
+                label: Some("post_process_effect_bind_group_layout"),
+            });
+
+        let effect = |settings: EffectSettings| {
+            let uniform_buffer =
+                device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} Buffer", settings.name())),
+                    contents: bytemuck::cast_slice(&[EffectUniform {
+                        params: settings.params(),
+                        texel_size: [0.0; 2],
+                        frame: 0,
+                        _padding: 0,
+                    }]),
+                    usage: egui_wgpu::wgpu::BufferUsages::UNIFORM
+                        | egui_wgpu::wgpu::BufferUsages::COPY_DST,
+                });
+            let bind_group = device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+                layout: &effect_layout,
+                entries: &[egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }],
+                label: Some("post_process_effect_bind_group"),
+            });
+            Effect {
+                enabled: false,
+                settings,
+                uniform_buffer,
+                bind_group,
+            }
+        };
+        let effects = vec![
+            effect(EffectSettings::Bloom {
+                threshold: 0.8,
+                intensity: 0.6,
+                radius: 1.5,
+            }),
+            // Before anything adding high frequency detail to the image
+            effect(EffectSettings::Fxaa {
+                subpixel: 0.75,
+                edge_threshold: 0.166,
+                edge_threshold_min: 0.0833,
+            }),
+            effect(EffectSettings::ChromaticAberration { strength: 4.0 }),
+            effect(EffectSettings::ColorGrading {
+                intensity: 1.0,
+                contrast: 1.1,
+                saturation: 1.2,
+                temperature: 0.2,
+                tint: 0.0,
+            }),
+            effect(EffectSettings::Vignette {
+                intensity: 0.6,
+                radius: 0.8,
+                smoothness: 0.5,
+            }),
+            effect(EffectSettings::FilmGrain { intensity: 0.08 }),
+        ];
+
+        let lut = create_lut(device);
+        let lut_bind_group = device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+            layout: &lut_layout,
+            entries: &[
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: egui_wgpu::wgpu::BindingResource::TextureView(&lut.view),
+                },
+                egui_wgpu::wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: egui_wgpu::wgpu::BindingResource::Sampler(&lut.sampler),
+                },
+            ],
+            label: Some("post_process_lut_bind_group"),
+        });
+
+        let shader =
+            device.create_shader_module(egui_wgpu::wgpu::include_wgsl!("postprocess.wgsl"));
+        let pipeline_layout =
+            device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+                label: Some("Post Process Pipeline Layout"),
+                bind_group_layouts: &[&input_layout, &effect_layout],
+                push_constant_ranges: &[],
+            });
+        let lut_pipeline_layout =
+            device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+                label: Some("Post Process LUT Pipeline Layout"),
+                bind_group_layouts: &[&input_layout, &effect_layout, &lut_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = |layout, entry_point, format, blend| {
+            create_pipeline(device, layout, &shader, entry_point, format, blend)
+        };
+        let additive = egui_wgpu::wgpu::BlendState {
+            color: egui_wgpu::wgpu::BlendComponent {
+                src_factor: egui_wgpu::wgpu::BlendFactor::One,
+                dst_factor: egui_wgpu::wgpu::BlendFactor::One,
+                operation: egui_wgpu::wgpu::BlendOperation::Add,
+            },
+            alpha: egui_wgpu::wgpu::BlendComponent::REPLACE,
+        };
+
+        let scene_input = create_input(device, &input_layout, hdr.texture());
+        let bloom_targets = create_targets(
+            device,
+            &input_layout,
+            config.width / 2,
+            config.height / 2,
+            hdr::HDR_FORMAT,
+            "bloom_texture",
+        );
+        let ldr_targets = create_targets(
+            device,
+            &input_layout,
+            config.width,
+            config.height,
+            config.format,
+            "post_process_texture",
+        );
+
+        Self {
+            effects,
+            scene_input,
+            bloom_targets,
+            ldr_targets,
+            format: config.format,
+            bright_pass: pipeline(&pipeline_layout, "fs_bright_pass", hdr::HDR_FORMAT, None),
+            blur_horizontal: pipeline(
+                &pipeline_layout,
+                "fs_blur_horizontal",
+                hdr::HDR_FORMAT,
+                None,
+            ),
+            blur_vertical: pipeline(&pipeline_layout, "fs_blur_vertical", hdr::HDR_FORMAT, None),
+            bloom_composite: pipeline(
+                &pipeline_layout,
+                "fs_bloom_composite",
+                hdr::HDR_FORMAT,
+                Some(additive),
+            ),
+            fxaa: pipeline(&pipeline_layout, "fs_fxaa", config.format, None),
+            vignette: pipeline(&pipeline_layout, "fs_vignette", config.format, None),
+            chromatic_aberration: pipeline(
+                &pipeline_layout,
+                "fs_chromatic_aberration",
+                config.format,
+                None,
+            ),
+            color_grading: pipeline(
+                &lut_pipeline_layout,
+                "fs_color_grading",
+                config.format,
+                None,
+            ),
+            film_grain: pipeline(&pipeline_layout, "fs_film_grain", config.format, None),
+            input_layout,
+            lut,
+            lut_bind_group,
+            lut_grade: None,
+            frame: 0,
+        }
+    }
+
+    /// The effects in the order they are applied. They can be reordered and
+    /// tweaked, but not added or removed.
+    pub fn effects_mut(&mut self) -> &mut [Effect] {
+        &mut self.effects
+    }
+
+    /// Recreates the targets at the new size. Must be called after the HDR
+    /// target was resized, as it reads from that.
+    pub fn resize(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        width: u32,
+        height: u32,
+        hdr: &hdr::HdrPipeline,
+    ) {
+        self.scene_input = create_input(device, &self.input_layout, hdr.texture());
+        self.bloom_targets = create_targets(
+            device,
+            &self.input_layout,
+            width / 2,
+            height / 2,
+            hdr::HDR_FORMAT,
+            "bloom_texture",
+        );
+        self.ldr_targets = create_targets(
+            device,
+            &self.input_layout,
+            width,
+            height,
+            self.format,
+            "post_process_texture",
+        );
+    }
+
+    /// Applies the HDR effects to the scene, tonemaps it and runs the rest of
+    /// the chain, leaving the result in `output`.
+    pub fn render(
+        &mut self,
+        queue: &egui_wgpu::wgpu::Queue,
+        encoder: &mut egui_wgpu::wgpu::CommandEncoder,
+        hdr: &hdr::HdrPipeline,
+        output: &egui_wgpu::wgpu::TextureView,
+    ) {
+        self.frame = self.frame.wrapping_add(1);
+        let texel_size = |target: &Target| {
+            let size = target.texture.texture.size();
+            [1.0 / size.width as f32, 1.0 / size.height as f32]
+        };
+        let bloom_texel_size = texel_size(&self.bloom_targets[0]);
+        let ldr_texel_size = texel_size(&self.ldr_targets[0]);
+
+        for effect in self.effects.iter().filter(|effect| effect.enabled) {
+            let texel_size = if effect.settings.is_hdr() {
+                bloom_texel_size
+            } else {
+                ldr_texel_size
+            };
+            queue.write_buffer(
+                &effect.uniform_buffer,
+                0,
+                bytemuck::cast_slice(&[EffectUniform {
+                    params: effect.settings.params(),
+                    texel_size,
+                    frame: self.frame,
+                    _padding: 0,
+                }]),
+            );
+
+            if let EffectSettings::ColorGrading {
+                contrast,
+                saturation,
+                temperature,
+                tint,
+                ..
+            } = effect.settings
+            {
+                let grade = [contrast, saturation, temperature, tint];
+                if self.lut_grade != Some(grade) {
+                    write_lut(queue, &self.lut, grade);
+                    self.lut_grade = Some(grade);
+                }
+            }
+        }
+
+        for effect in self
+            .effects
+            .iter()
+            .filter(|effect| effect.enabled && effect.settings.is_hdr())
+        {
+            let [first, second] = &self.bloom_targets;
+            let clear = egui_wgpu::wgpu::LoadOp::Clear(egui_wgpu::wgpu::Color::BLACK);
+            draw(
+                encoder,
+                "bright_pass",
+                &self.bright_pass,
+                &[&self.scene_input, &effect.bind_group],
+                &first.texture.view,
+                clear,
+            );
+            draw(
+                encoder,
+                "blur_horizontal",
+                &self.blur_horizontal,
+                &[&first.bind_group, &effect.bind_group],
+                &second.texture.view,
+                clear,
+            );
+            draw(
+                encoder,
+                "blur_vertical",
+                &self.blur_vertical,
+                &[&second.bind_group, &effect.bind_group],
+                &first.texture.view,
+                clear,
+            );
+            draw(
+                encoder,
+                "bloom_composite",
+                &self.bloom_composite,
+                &[&first.bind_group, &effect.bind_group],
+                hdr.view(),
+                egui_wgpu::wgpu::LoadOp::Load,
+            );
+        }
+
+        let ldr = self
+            .effects
+            .iter()
+            .filter(|effect| effect.enabled && !effect.settings.is_hdr())
+            .collect::<Vec<_>>();
+        if ldr.is_empty() {
+            hdr.process(queue, encoder, output);
+            return;
+        }
+
+        hdr.process(queue, encoder, &self.ldr_targets[0].texture.view);
+        for (i, effect) in ldr.iter().enumerate() {
+            let input = &self.ldr_targets[i % 2];
+            let target = if i + 1 == ldr.len() {
+                output
+            } else {
+                &self.ldr_targets[(i + 1) % 2].texture.view
+            };
+            let (pipeline, lut) = match effect.settings {
+                EffectSettings::Bloom { .. } => unreachable!(),
+                EffectSettings::Fxaa { .. } => (&self.fxaa, None),
+                EffectSettings::Vignette { .. } => (&self.vignette, None),
+                EffectSettings::ChromaticAberration { .. } => (&self.chromatic_aberration, None),
+                EffectSettings::ColorGrading { .. } => {
+                    (&self.color_grading, Some(&self.lut_bind_group))
+                }
+                EffectSettings::FilmGrain { .. } => (&self.film_grain, None),
+            };
+            let mut bind_groups = vec![&input.bind_group, &effect.bind_group];
+            bind_groups.extend(lut);
+            draw(
+                encoder,
+                effect.settings.name(),
+                pipeline,
+                &bind_groups,
+                target,
+                egui_wgpu::wgpu::LoadOp::Clear(egui_wgpu::wgpu::Color::BLACK),
+            );
+        }
+    }
+}
+
+fn create_input(
+    device: &egui_wgpu::wgpu::Device,
+    layout: &egui_wgpu::wgpu::BindGroupLayout,
+    texture: &texture::Texture,
+) -> egui_wgpu::wgpu::BindGroup {
+    device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            egui_wgpu::wgpu::BindGroupEntry {
+                binding: 0,
+                resource: egui_wgpu::wgpu::BindingResource::TextureView(&texture.view),
+            },
+            egui_wgpu::wgpu::BindGroupEntry {
+                binding: 1,
+                resource: egui_wgpu::wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+        ],
+        label: Some("post_process_input_bind_group"),
+    })
+}
+
+fn create_targets(
+    device: &egui_wgpu::wgpu::Device,
+    layout: &egui_wgpu::wgpu::BindGroupLayout,
+    width: u32,
+    height: u32,
+    format: egui_wgpu::wgpu::TextureFormat,
+    label: &str,
+) -> [Target; 2] {
+    [0, 1].map(|i| {
+        let texture = texture::Texture::create_render_target(
+            device,
+            width,
+            height,
+            format,
+            &format!("{}_{}", label, i),
+        );
+        let bind_group = create_input(device, layout, &texture);
+        Target {
+            texture,
+            bind_group,
+        }
+    })
+}
+
+pub(crate) fn create_pipeline(
+    device: &egui_wgpu::wgpu::Device,
+    layout: &egui_wgpu::wgpu::PipelineLayout,
+    shader: &egui_wgpu::wgpu::ShaderModule,
+    entry_point: &str,
+    format: egui_wgpu::wgpu::TextureFormat,
+    blend: Option<egui_wgpu::wgpu::BlendState>,
+) -> egui_wgpu::wgpu::RenderPipeline {
+    device.create_render_pipeline(&egui_wgpu::wgpu::RenderPipelineDescriptor {
+        label: Some(entry_point),
+        layout: Some(layout),
+        vertex: egui_wgpu::wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(egui_wgpu::wgpu::FragmentState {
+            module: shader,
+            entry_point,
+            targets: &[Some(egui_wgpu::wgpu::ColorTargetState {
+                format,
+                blend,
+                write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: egui_wgpu::wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: egui_wgpu::wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+/// Draws a full screen triangle with `pipeline` into `output`.
+pub(crate) fn draw(
+    encoder: &mut egui_wgpu::wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &egui_wgpu::wgpu::RenderPipeline,
+    bind_groups: &[&egui_wgpu::wgpu::BindGroup],
+    output: &egui_wgpu::wgpu::TextureView,
+    load: egui_wgpu::wgpu::LoadOp<egui_wgpu::wgpu::Color>,
+) {
+    let mut pass = encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(egui_wgpu::wgpu::RenderPassColorAttachment {
+            view: output,
+            resolve_target: None,
+            ops: egui_wgpu::wgpu::Operations {
+                load,
+                store: egui_wgpu::wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+    pass.set_pipeline(pipeline);
+    for (i, bind_group) in bind_groups.iter().enumerate() {
+        pass.set_bind_group(i as u32, bind_group, &[]);
+    }
+    pass.draw(0..3, 0..1);
+}
+
+/// 3D texture indexed by sRGB encoded color. Stored as sRGB so that sampling
+/// it returns linear color, with the precision where it's needed.
+fn create_lut(device: &egui_wgpu::wgpu::Device) -> texture::Texture {
+    let texture = device.create_texture(&egui_wgpu::wgpu::TextureDescriptor {
+        label: Some("color_grading_lut"),
+        size: egui_wgpu::wgpu::Extent3d {
+            width: LUT_SIZE,
+            height: LUT_SIZE,
+            depth_or_array_layers: LUT_SIZE,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: egui_wgpu::wgpu::TextureDimension::D3,
+        format: egui_wgpu::wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: egui_wgpu::wgpu::TextureUsages::TEXTURE_BINDING
+            | egui_wgpu::wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&egui_wgpu::wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&egui_wgpu::wgpu::SamplerDescriptor {
+        address_mode_u: egui_wgpu::wgpu::AddressMode::ClampToEdge,
+        address_mode_v: egui_wgpu::wgpu::AddressMode::ClampToEdge,
+        address_mode_w: egui_wgpu::wgpu::AddressMode::ClampToEdge,
+        mag_filter: egui_wgpu::wgpu::FilterMode::Linear,
+        min_filter: egui_wgpu::wgpu::FilterMode::Linear,
+        mipmap_filter: egui_wgpu::wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+    texture::Texture {
+        texture,
+        view,
+        sampler,
+    }
+}
+
+/// Bakes `[contrast, saturation, temperature, tint]` into the LUT.
+fn write_lut(queue: &egui_wgpu::wgpu::Queue, lut: &texture::Texture, grade: [f32; 4]) {
+    let [contrast, saturation, temperature, tint] = grade;
+    let to_linear = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let to_srgb = |c: f32| {
+        if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    };
+
+    let max = (LUT_SIZE - 1) as f32;
+    let mut data = Vec::with_capacity((LUT_SIZE * LUT_SIZE * LUT_SIZE * 4) as usize);
+    for b in 0..LUT_SIZE {
+        for g in 0..LUT_SIZE {
+            for r in 0..LUT_SIZE {
+                let mut color = [r, g, b].map(|c| to_linear(c as f32 / max));
+                // White balance in linear space
+                color[0] *= 1.0 + 0.2 * temperature;
+                color[1] *= 1.0 - 0.2 * tint;
+                color[2] *= 1.0 - 0.2 * temperature;
+                let luma = 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+                let color = color.map(|c| {
+                    let c = luma + (c - luma) * saturation;
+                    // Contrast is applied perceptually, pivoting around middle gray
+                    let c = (to_srgb(c.max(0.0)) - 0.5) * contrast + 0.5;
+                    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+                });
+                data.extend_from_slice(&[color[0], color[1], color[2], 255]);
+            }
+        }
+    }
+
+    queue.write_texture(
+        egui_wgpu::wgpu::ImageCopyTexture {
+            aspect: egui_wgpu::wgpu::TextureAspect::All,
+            texture: &lut.texture,
+            mip_level: 0,
+            origin: egui_wgpu::wgpu::Origin3d::ZERO,
+        },
+        &data,
+        egui_wgpu::wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * LUT_SIZE),
+            rows_per_image: Some(LUT_SIZE),
+        },
+        lut.texture.size(),
+    );
+}
@@ -0,0 +1,263 @@
+// Full screen post-processing effects
+//
+// Every effect reads the previous target through group 0 and its settings
+// through group 1. Bloom runs on the HDR scene, everything else runs on the
+// tonemapped image.
+
+struct Effect {
+    params: vec4<f32>,
+    texel_size: vec2<f32>,
+    frame: u32,
+}
+
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+@group(1) @binding(0)
+var<uniform> effect: Effect;
+@group(2) @binding(0)
+var t_lut: texture_3d<f32>;
+@group(2) @binding(1)
+var s_lut: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// A single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Bloom, params: threshold, intensity, radius
+
+@fragment
+fn fs_bright_pass(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+    let contribution = max(brightness - effect.params.x, 0.0) / max(brightness, 1e-4);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+fn blur(uv: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
+    // 9 tap gaussian folded into 5 bilinear samples
+    var offsets = array<f32, 3>(0.0, 1.3846153846, 3.2307692308);
+    var weights = array<f32, 3>(0.2270270270, 0.3162162162, 0.0702702703);
+    let step = direction * effect.texel_size * effect.params.z;
+
+    var color = textureSample(t_input, s_input, uv).rgb * weights[0];
+    for (var i = 1; i < 3; i++) {
+        color += textureSample(t_input, s_input, uv + step * offsets[i]).rgb * weights[i];
+        color += textureSample(t_input, s_input, uv - step * offsets[i]).rgb * weights[i];
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+@fragment
+fn fs_blur_horizontal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return blur(in.uv, vec2<f32>(1.0, 0.0));
+}
+
+@fragment
+fn fs_blur_vertical(in: VertexOutput) -> @location(0) vec4<f32> {
+    return blur(in.uv, vec2<f32>(0.0, 1.0));
+}
+
+// Blended additively onto the scene
+@fragment
+fn fs_bloom_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let bloom = textureSample(t_input, s_input, in.uv).rgb;
+    return vec4<f32>(bloom * effect.params.y, 1.0);
+}
+
+// Vignette, params: intensity, radius, smoothness
+
+@fragment
+fn fs_vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+    // Distance from the center, 1 in the corners
+    let distance = length(in.uv - 0.5) * 1.41421356;
+    let falloff = smoothstep(effect.params.y, effect.params.y - effect.params.z, distance);
+    return vec4<f32>(color.rgb * mix(1.0, falloff, effect.params.x), color.a);
+}
+
+// Chromatic aberration, params: strength in pixels at the screen edge
+
+@fragment
+fn fs_chromatic_aberration(in: VertexOutput) -> @location(0) vec4<f32> {
+    let offset = (in.uv - 0.5) * 2.0 * effect.texel_size * effect.params.x;
+    let r = textureSample(t_input, s_input, in.uv + offset).r;
+    let ga = textureSample(t_input, s_input, in.uv).ga;
+    let b = textureSample(t_input, s_input, in.uv - offset).b;
+    return vec4<f32>(r, ga.x, b, ga.y);
+}
+
+// Color grading, params: intensity. The grade itself is baked into the LUT,
+// which is indexed by sRGB encoded color.
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_color_grading(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+    let size = f32(textureDimensions(t_lut).x);
+    let encoded = linear_to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+    // Sample texel centers so the ends of the range aren't blended with the
+    // clamped border
+    let uvw = encoded * ((size - 1.0) / size) + 0.5 / size;
+    let graded = textureSample(t_lut, s_lut, uvw).rgb;
+    return vec4<f32>(mix(color.rgb, graded, effect.params.x), color.a);
+}
+
+// Film grain, params: intensity
+
+fn hash(p: vec3<u32>) -> f32 {
+    // PCG based hash from "Hash Functions for GPU Rendering"
+    var v = p * 1664525u + 1013904223u;
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    v ^= v >> vec3<u32>(16u);
+    v.x += v.y * v.z;
+    return f32(v.x) / 4294967295.0;
+}
+
+@fragment
+fn fs_film_grain(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+    let noise = hash(vec3<u32>(vec2<u32>(in.clip_position.xy), effect.frame)) - 0.5;
+    // Grain is most visible in the mid tones
+    let luma = luminance(color.rgb);
+    let amount = effect.params.x * (1.0 - abs(luma * 2.0 - 1.0));
+    return vec4<f32>(max(color.rgb + noise * amount, vec3<f32>(0.0)), color.a);
+}
+
+// FXAA, params: subpixel blending, edge threshold, minimum edge threshold.
+// Follows the structure of FXAA 3.11 quality by Timothy Lottes.
+
+fn fxaa_luma(uv: vec2<f32>) -> f32 {
+    // Edges are detected on perceptual luma, the input is linear
+    return sqrt(luminance(textureSampleLevel(t_input, s_input, uv, 0.0).rgb));
+}
+
+const FXAA_STEPS: i32 = 10;
+
+@fragment
+fn fs_fxaa(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = effect.texel_size;
+    let uv = in.uv;
+    let center = textureSampleLevel(t_input, s_input, uv, 0.0);
+
+    let luma_m = sqrt(luminance(center.rgb));
+    let luma_n = fxaa_luma(uv + vec2<f32>(0.0, -texel.y));
+    let luma_s = fxaa_luma(uv + vec2<f32>(0.0, texel.y));
+    let luma_e = fxaa_luma(uv + vec2<f32>(texel.x, 0.0));
+    let luma_w = fxaa_luma(uv + vec2<f32>(-texel.x, 0.0));
+
+    let luma_max = max(luma_m, max(max(luma_n, luma_s), max(luma_e, luma_w)));
+    let luma_min = min(luma_m, min(min(luma_n, luma_s), min(luma_e, luma_w)));
+    let range = luma_max - luma_min;
+    if range < max(effect.params.z, luma_max * effect.params.y) {
+        return center;
+    }
+
+    let luma_nw = fxaa_luma(uv + vec2<f32>(-texel.x, -texel.y));
+    let luma_ne = fxaa_luma(uv + vec2<f32>(texel.x, -texel.y));
+    let luma_sw = fxaa_luma(uv + vec2<f32>(-texel.x, texel.y));
+    let luma_se = fxaa_luma(uv + vec2<f32>(texel.x, texel.y));
+
+    // Sub-pixel aliasing, how much the center differs from its neighbourhood
+    let average = (2.0 * (luma_n + luma_s + luma_e + luma_w) + luma_nw + luma_ne + luma_sw + luma_se) / 12.0;
+    let subpixel = smoothstep(0.0, 1.0, clamp(abs(average - luma_m) / range, 0.0, 1.0));
+    let subpixel_blend = subpixel * subpixel * effect.params.x;
+
+    let horizontal = abs(luma_nw + luma_ne - 2.0 * luma_n) + 2.0 * abs(luma_w + luma_e - 2.0 * luma_m) + abs(luma_sw + luma_se - 2.0 * luma_s);
+    let vertical = abs(luma_nw + luma_sw - 2.0 * luma_w) + 2.0 * abs(luma_n + luma_s - 2.0 * luma_m) + abs(luma_ne + luma_se - 2.0 * luma_e);
+    let is_horizontal = horizontal >= vertical;
+
+    // Step across the edge towards the side with the larger gradient
+    var step_length = select(texel.x, texel.y, is_horizontal);
+    let luma_positive = select(luma_e, luma_s, is_horizontal);
+    let luma_negative = select(luma_w, luma_n, is_horizontal);
+    let gradient_positive = abs(luma_positive - luma_m);
+    let gradient_negative = abs(luma_negative - luma_m);
+    var luma_opposite = luma_positive;
+    var gradient = gradient_positive;
+    if gradient_negative > gradient_positive {
+        step_length = -step_length;
+        luma_opposite = luma_negative;
+        gradient = gradient_negative;
+    }
+
+    var edge_uv = uv;
+    if is_horizontal {
+        edge_uv.y += step_length * 0.5;
+    } else {
+        edge_uv.x += step_length * 0.5;
+    }
+    let edge_step = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+
+    // Walk along the edge in both directions until its end
+    let edge_luma = (luma_m + luma_opposite) * 0.5;
+    let threshold = gradient * 0.25;
+    var uv_positive = edge_uv + edge_step;
+    var uv_negative = edge_uv - edge_step;
+    var delta_positive = fxaa_luma(uv_positive) - edge_luma;
+    var delta_negative = fxaa_luma(uv_negative) - edge_luma;
+    var done_positive = abs(delta_positive) >= threshold;
+    var done_negative = abs(delta_negative) >= threshold;
+    for (var i = 0; i < FXAA_STEPS; i++) {
+        if done_positive && done_negative {
+            break;
+        }
+        let stride = select(1.5, 2.0, i > 2);
+        if !done_positive {
+            uv_positive += edge_step * stride;
+            delta_positive = fxaa_luma(uv_positive) - edge_luma;
+            done_positive = abs(delta_positive) >= threshold;
+        }
+        if !done_negative {
+            uv_negative -= edge_step * stride;
+            delta_negative = fxaa_luma(uv_negative) - edge_luma;
+            done_negative = abs(delta_negative) >= threshold;
+        }
+    }
+
+    let distance_positive = select(uv_positive.y - uv.y, uv_positive.x - uv.x, is_horizontal);
+    let distance_negative = select(uv.y - uv_negative.y, uv.x - uv_negative.x, is_horizontal);
+    let closest_positive = distance_positive < distance_negative;
+    let distance = min(distance_positive, distance_negative);
+    let edge_length = distance_positive + distance_negative;
+
+    // Only blend if the center is on the side of the edge the end belongs to
+    let center_below = luma_m - edge_luma < 0.0;
+    let end_delta = select(delta_negative, delta_positive, closest_positive);
+    var edge_blend = 0.0;
+    if (end_delta < 0.0) != center_below {
+        edge_blend = 0.5 - distance / edge_length;
+    }
+
+    let blend = max(edge_blend, subpixel_blend);
+    var final_uv = uv;
+    if is_horizontal {
+        final_uv.y += blend * step_length;
+    } else {
+        final_uv.x += blend * step_length;
+    }
+    return textureSampleLevel(t_input, s_input, final_uv, 0.0);
+}
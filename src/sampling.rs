@@ -0,0 +1,32 @@
+/// Element `index` of the Halton sequence in `base`, the digits of `index`
+/// mirrored around the radix point. Consecutive elements in coprime bases
+/// fill the unit square or cube evenly without clumping, unlike random
+/// points. Index 0 is always 0, so sequences usually start at 1.
+pub fn halton(mut index: usize, base: usize) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirrors_digits() {
+        let base_2 = (1..=4).map(|i| halton(i, 2)).collect::<Vec<_>>();
+        assert_eq!(base_2, [0.5, 0.25, 0.75, 0.125]);
+        let base_3 = (1..=4).map(|i| halton(i, 3)).collect::<Vec<_>>();
+        for (a, b) in base_3
+            .iter()
+            .zip([1.0 / 3.0, 2.0 / 3.0, 1.0 / 9.0, 4.0 / 9.0])
+        {
+            assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use anyhow::{bail, Context, Result};
+
+/// A frame being copied out of the surface texture, saved as PNG once the
+/// GPU is done with it.
+pub struct Screenshot {
+    buffer: egui_wgpu::wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    bgra: bool,
+}
+
+impl Screenshot {
+    /// Records copying `texture` at this point of `encoder`. Needs a texture
+    /// with `COPY_SRC` usage in an 8 bit RGBA or BGRA format.
+    pub fn capture(
+        device: &egui_wgpu::wgpu::Device,
+        encoder: &mut egui_wgpu::wgpu::CommandEncoder,
+        texture: &egui_wgpu::wgpu::Texture,
+    ) -> Result<Self> {
+        if !texture
+            .usage()
+            .contains(egui_wgpu::wgpu::TextureUsages::COPY_SRC)
+        {
+            bail!("The surface doesn't support copying frames out of it");
+        }
+        let bgra = match texture.format() {
+            egui_wgpu::wgpu::TextureFormat::Rgba8Unorm
+            | egui_wgpu::wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            egui_wgpu::wgpu::TextureFormat::Bgra8Unorm
+            | egui_wgpu::wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => bail!("Screenshots of {:?} surfaces aren't supported", format),
+        };
+
+        let (width, height) = (texture.width(), texture.height());
+        // Rows of a buffer copy need to be aligned
+        let padded_bytes_per_row =
+            (width * 4).next_multiple_of(egui_wgpu::wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&egui_wgpu::wgpu::BufferDescriptor {
+            label: Some("Screenshot Buffer"),
+            size: (padded_bytes_per_row * height) as egui_wgpu::wgpu::BufferAddress,
+            usage: egui_wgpu::wgpu::BufferUsages::COPY_DST
+                | egui_wgpu::wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            egui_wgpu::wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: egui_wgpu::wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+
+        Ok(Self {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            bgra,
+        })
+    }
+
+    /// Waits for the copy, which has to be submitted by now, and writes it to
+    /// `path`.
+    pub fn save(self, device: &egui_wgpu::wgpu::Device, path: &std::path::Path) -> Result<()> {
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(egui_wgpu::wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(egui_wgpu::wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .context("The screenshot buffer was never mapped")?
+            .context("Failed to map the screenshot buffer")?;
+
+        let mut pixels = Vec::with_capacity((self.width * self.height * 4) as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks(self.padded_bytes_per_row as usize)
+        {
+            for pixel in row[..(self.width * 4) as usize].chunks(4) {
+                let [r, g, b] = if self.bgra {
+                    [pixel[2], pixel[1], pixel[0]]
+                } else {
+                    [pixel[0], pixel[1], pixel[2]]
+                };
+                // The surface's alpha isn't meaningful
+                pixels.extend_from_slice(&[r, g, b, 255]);
+            }
+        }
+        self.buffer.unmap();
+
+        image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .context("Screenshot size mismatch")?
+            .save(path)
+            .with_context(|| format!("Failed to save screenshot {}", path.display()))
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{camera, text_file};
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Where settings are kept, relative to the working directory, next to
+/// `input::BINDINGS_FILE`.
+pub const SETTINGS_FILE: &str = "settings.txt";
+
+/// Settings kept across runs.
+///
+/// Saved as text with one setting per line, its name followed by its value,
+/// as in `DepthMode ReverseZ`. Settings missing from the file keep their
+/// defaults.
+#[derive(Debug)]
+pub struct Settings {
+    pub depth_mode: camera::DepthMode,
+    path: PathBuf,
+}
+
+impl Settings {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            depth_mode: camera::DepthMode::Standard,
+            path: path.into(),
+        }
+    }
+
+    /// Reads the settings at `path`, or starts from the defaults if there is
+    /// no such file.
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let mut settings = Self::new(path);
+        if !settings.path.exists() {
+            return Ok(settings);
+        }
+
+        for (number, line) in text_file::read_lines(&settings.path, "settings")? {
+            let (name, value) = line.split_once(' ').unwrap_or((&line, ""));
+            match name {
+                "DepthMode" => {
+                    let Some(depth_mode) = camera::DepthMode::ALL
+                        .into_iter()
+                        .find(|mode| format!("{:?}", mode) == value.trim())
+                    else {
+                        bail!("Unknown depth mode {} on line {}", value, number);
+                    };
+                    settings.depth_mode = depth_mode;
+                }
+                _ => bail!("Unknown setting {} on line {}", name, number),
+            }
+        }
+        Ok(settings)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let text = format!(
+            "# Setting followed by its value\nDepthMode {:?}\n",
+            self.depth_mode
+        );
+        std::fs::write(&self.path, text)
+            .with_context(|| format!("Failed to write settings {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_file::temp_path;
+
+    #[test]
+    fn depth_mode_round_trips_through_file() {
+        let path = temp_path("settings.txt");
+        for depth_mode in camera::DepthMode::ALL {
+            let mut settings = Settings::new(&path);
+            settings.depth_mode = depth_mode;
+            settings.save().unwrap();
+            assert_eq!(Settings::load(&path).unwrap().depth_mode, depth_mode);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_depth_modes() {
+        let path = temp_path("unknown-depth-mode.txt");
+        std::fs::write(&path, "DepthMode Inverted\n").unwrap();
+        let result = Settings::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -1,25 +1,38 @@
 // Vertex shader
-
-struct Camera {
-    view_proj: mat4x4<f32>,
-}
-@group(1) @binding(0)
-var<uniform> camera: Camera;
+//
+// The camera and lights are declared in lighting.wgsl.
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
 }
 struct InstanceInput {
     @location(5) model_matrix_0: vec4<f32>,
     @location(6) model_matrix_1: vec4<f32>,
     @location(7) model_matrix_2: vec4<f32>,
     @location(8) model_matrix_3: vec4<f32>,
+    // The w components of the first two columns are the specular strength
+    // and shininess
+    @location(9) normal_matrix_0: vec4<f32>,
+    @location(10) normal_matrix_1: vec4<f32>,
+    @location(11) normal_matrix_2: vec4<f32>,
+    @location(12) tint: vec4<f32>,
+    @location(13) custom: vec4<f32>,
+    @location(14) layer: u32,
+    @location(15) lod_fade: f32,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) tint: vec4<f32>,
+    @location(3) custom: vec4<f32>,
+    @location(4) @interpolate(flat) layer: u32,
+    @location(5) @interpolate(flat) lod_fade: f32,
+    @location(6) world_position: vec3<f32>,
+    @location(7) @interpolate(flat) material: vec2<f32>,
 }
 
 @vertex
@@ -33,9 +46,22 @@ fn vs_main(
         instance.model_matrix_2,
         instance.model_matrix_3,
     );
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0.xyz,
+        instance.normal_matrix_1.xyz,
+        instance.normal_matrix_2.xyz,
+    );
     var out: VertexOutput;
-    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
     out.tex_coords = model.tex_coords;
+    out.world_normal = normalize(normal_matrix * model.normal);
+    out.tint = instance.tint;
+    out.custom = instance.custom;
+    out.layer = instance.layer;
+    out.lod_fade = instance.lod_fade;
+    out.material = vec2<f32>(instance.normal_matrix_0.w, instance.normal_matrix_1.w);
     return out;
 }
 
@@ -46,17 +72,169 @@ fn vs_main(
 // Fragment shader
 
 @group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
+var t_diffuse: texture_2d_array<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
-@group(0) @binding(2)
-var t_depth: texture_depth_2d;
 
+// Ordered dithering threshold in (0, 1) for the given pixel
+fn bayer4x4(pixel: vec2<u32>) -> f32 {
+    var matrix = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0,
+    );
+    return (matrix[(pixel.y % 4u) * 4u + pixel.x % 4u] + 0.5) / 16.0;
+}
+
+// An instance fading out keeps the pixels above the threshold and the same
+// instance fading in keeps the ones below, so together they cover every pixel
+// exactly once.
+fn lod_dither_discard(lod_fade: f32, position: vec4<f32>) -> bool {
+    if lod_fade == 0.0 {
+        return false;
+    }
+    let threshold = bayer4x4(vec2<u32>(position.xy));
+    if lod_fade > 0.0 {
+        return threshold < lod_fade;
+    }
+    return threshold >= -lod_fade;
+}
+
+// Light clusters, filled by cluster.wgsl
+struct ClusterParams {
+    view: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    screen_size: vec2<f32>,
+    znear: f32,
+    zfar: f32,
+    grid: vec3<u32>,
+    max_lights: u32,
+}
+
+@group(3) @binding(0)
+var<uniform> cluster_params: ClusterParams;
+@group(3) @binding(1)
+var<storage, read> clusters: array<u32>;
+
+// Has to match the slicing in cluster.wgsl
+fn cluster_index(frag_coord: vec2<f32>, world_position: vec3<f32>) -> u32 {
+    let grid = cluster_params.grid;
+    let depth = -(cluster_params.view * vec4<f32>(world_position, 1.0)).z;
+    let depth_range = log(cluster_params.zfar / cluster_params.znear);
+    let slice = max(log(depth / cluster_params.znear) / depth_range * f32(grid.z), 0.0);
+    let tile = frag_coord / cluster_params.screen_size * vec2<f32>(grid.xy);
+    let cell = min(vec3<u32>(vec2<u32>(tile), u32(slice)), grid - 1u);
+    return (cell.z * grid.y + cell.y) * grid.x + cell.x;
+}
+
+// Like `shade`, but only with the point lights of the fragment's cluster
+fn shade_clustered(surface: Surface, frag_coord: vec2<f32>) -> vec3<f32> {
+    let view_dir = normalize(camera.position.xyz - surface.position);
+    var color = shade_directional(surface, view_dir);
+    let base = cluster_index(frag_coord, surface.position) * (cluster_params.max_lights + 1u);
+    for (var i = 0u; i < clusters[base]; i++) {
+        color += point_light_contribution(surface, view_dir, point_lights[clusters[base + 1u + i]]);
+    }
+    return color;
+}
+
+fn albedo(in: VertexOutput) -> vec4<f32> {
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords, in.layer) * in.tint;
+}
+
+// Texels below this alpha are cut out by the cutout shaders
+const ALPHA_CUTOFF: f32 = 0.5;
+
+fn shade_forward(in: VertexOutput, albedo: vec4<f32>) -> vec4<f32> {
+    var surface: Surface;
+    surface.position = in.world_position;
+    surface.normal = normalize(in.world_normal);
+    surface.albedo = albedo.rgb;
+    surface.specular = in.material.x;
+    surface.shininess = in.material.y;
+    return vec4<f32>(shade_clustered(surface, in.clip_position.xy), albedo.a);
+}
+
+// Forward path, lights every fragment as it is drawn. Also used for sorted
+// alpha blending.
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let near = 0.1;
-    let far = 100.0;
-    let depth = textureSample(t_depth, s_diffuse, in.tex_coords).x;
-    let r = (2.0 * near) / (far + near - depth * (far - near));
-    return vec4<f32>(vec3<f32>(r), 1.0);
+    if lod_dither_discard(in.lod_fade, in.clip_position) {
+        discard;
+    }
+    return shade_forward(in, albedo(in));
+}
+
+// Alpha tested, for single sampled targets
+@fragment
+fn fs_cutout(in: VertexOutput) -> @location(0) vec4<f32> {
+    let albedo = albedo(in);
+    if lod_dither_discard(in.lod_fade, in.clip_position) || albedo.a < ALPHA_CUTOFF {
+        discard;
+    }
+    return vec4<f32>(shade_forward(in, albedo).rgb, 1.0);
+}
+
+// Alpha tested through alpha to coverage with MSAA. The alpha is sharpened
+// into a ramp about a pixel wide around the cutoff, which the coverage mask
+// turns into antialiased edges.
+@fragment
+fn fs_cutout_coverage(in: VertexOutput) -> @location(0) vec4<f32> {
+    if lod_dither_discard(in.lod_fade, in.clip_position) {
+        discard;
+    }
+    let albedo = albedo(in);
+    let alpha = (albedo.a - ALPHA_CUTOFF) / max(fwidth(albedo.a), 1e-4) + 0.5;
+    return vec4<f32>(shade_forward(in, albedo).rgb, alpha);
+}
+
+// Shadow casters drawn with `AlphaMode::Cutout`, only writing depth
+@fragment
+fn fs_shadow_cutout(in: VertexOutput) {
+    if lod_dither_discard(in.lod_fade, in.clip_position) || albedo(in).a < ALPHA_CUTOFF {
+        discard;
+    }
+}
+
+// Weighted blended order-independent transparency, composited by oit.wgsl
+struct AccumulateOutput {
+    @location(0) accum: vec4<f32>,
+    @location(1) revealage: f32,
+}
+
+@fragment
+fn fs_accumulate(in: VertexOutput) -> AccumulateOutput {
+    if lod_dither_discard(in.lod_fade, in.clip_position) {
+        discard;
+    }
+    let color = shade_forward(in, albedo(in));
+    // Nearer and more opaque surfaces weigh more, from McGuire and Bavoil's
+    // paper. Uses the distance to the camera rather than the depth buffer
+    // value, which depends on the depth mode.
+    let distance = length(camera.position.xyz - in.world_position);
+    let weight = clamp(color.a * 0.03 / (1e-5 + pow(distance / 200.0, 4.0)), 1e-2, 3e3);
+    var out: AccumulateOutput;
+    out.accum = vec4<f32>(color.rgb * color.a, color.a) * weight;
+    out.revealage = color.a;
+    return out;
+}
+
+// Deferred path, see deferred.wgsl for the layout
+struct GBufferOutput {
+    @location(0) albedo: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+    @location(2) material: vec4<f32>,
+}
+
+@fragment
+fn fs_gbuffer(in: VertexOutput) -> GBufferOutput {
+    if lod_dither_discard(in.lod_fade, in.clip_position) {
+        discard;
+    }
+    var out: GBufferOutput;
+    out.albedo = albedo(in);
+    out.normal = vec4<f32>(normalize(in.world_normal), 0.0);
+    out.material = vec4<f32>(in.material.x, in.material.y / MAX_SHININESS, 0.0, 0.0);
+    return out;
 }
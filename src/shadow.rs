@@ -0,0 +1,206 @@
+use crate::{bounds, instance, mesh, state, texture};
+use cgmath::prelude::*;
+use egui_wgpu::wgpu::util::DeviceExt;
+
+/// Resolution of the sun's shadow map along each side.
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowParams {
+    light_view_proj: [[f32; 4]; 4],
+    bias: f32,
+    enabled: u32,
+    texel_size: f32,
+    _padding: u32,
+}
+
+/// Shadow map of the sun, rendered with an orthographic projection fitted
+/// around the scene. Lighting reads it through the bindings `Lights` adds to
+/// its bind group.
+pub struct ShadowMap {
+    pub enabled: bool,
+    /// Subtracted from the depth compared against the shadow map, against
+    /// self shadowing.
+    pub bias: f32,
+    pub(crate) texture: texture::Texture,
+    pub(crate) params_buffer: egui_wgpu::wgpu::Buffer,
+    /// The sun's view, laid out like the scene camera so that the scene's
+    /// vertex shader can draw the casters.
+    camera_buffer: egui_wgpu::wgpu::Buffer,
+    camera_bind_group: egui_wgpu::wgpu::BindGroup,
+    opaque_pipeline: egui_wgpu::wgpu::RenderPipeline,
+    cutout_pipeline: egui_wgpu::wgpu::RenderPipeline,
+}
+
+impl ShadowMap {
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        texture_layout: &egui_wgpu::wgpu::BindGroupLayout,
+        camera_layout: &egui_wgpu::wgpu::BindGroupLayout,
+        scene_shader: &egui_wgpu::wgpu::ShaderModule,
+    ) -> Self {
+        let texture = texture::Texture::create_sized_depth_texture(
+            device,
+            SHADOW_MAP_SIZE,
+            SHADOW_MAP_SIZE,
+            1,
+            "shadow_map",
+        );
+        let params_buffer = device.create_buffer(&egui_wgpu::wgpu::BufferDescriptor {
+            label: Some("Shadow Params Buffer"),
+            size: std::mem::size_of::<ShadowParams>() as egui_wgpu::wgpu::BufferAddress,
+            usage: egui_wgpu::wgpu::BufferUsages::UNIFORM | egui_wgpu::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_buffer =
+            device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
+                label: Some("Shadow Camera Buffer"),
+                contents: bytemuck::cast_slice(&[
+                    <state::CameraUniform as bytemuck::Zeroable>::zeroed(),
+                ]),
+                usage: egui_wgpu::wgpu::BufferUsages::UNIFORM
+                    | egui_wgpu::wgpu::BufferUsages::COPY_DST,
+            });
+        let camera_bind_group = device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+            layout: camera_layout,
+            entries: &[egui_wgpu::wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("shadow_camera_bind_group"),
+        });
+
+        let layout = device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[texture_layout, camera_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, fragment_entry_point: Option<&str>| {
+            device.create_render_pipeline(&egui_wgpu::wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: egui_wgpu::wgpu::VertexState {
+                    module: scene_shader,
+                    entry_point: "vs_main",
+                    buffers: &[mesh::Vertex::desc(), instance::InstanceRaw::desc()],
+                },
+                fragment: fragment_entry_point.map(|entry_point| egui_wgpu::wgpu::FragmentState {
+                    module: scene_shader,
+                    entry_point,
+                    targets: &[],
+                }),
+                // The quads are single sided, but cast shadows from both sides
+                primitive: egui_wgpu::wgpu::PrimitiveState {
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: Some(egui_wgpu::wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: egui_wgpu::wgpu::CompareFunction::Less,
+                    stencil: egui_wgpu::wgpu::StencilState::default(),
+                    bias: egui_wgpu::wgpu::DepthBiasState {
+                        constant: 2,
+                        slope_scale: 2.0,
+                        clamp: 0.0,
+                    },
+                }),
+                multisample: egui_wgpu::wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let opaque_pipeline = pipeline("Shadow Pipeline", None);
+        let cutout_pipeline = pipeline("Shadow Cutout Pipeline", Some("fs_shadow_cutout"));
+
+        Self {
+            enabled: true,
+            bias: 0.001,
+            texture,
+            params_buffer,
+            camera_buffer,
+            camera_bind_group,
+            opaque_pipeline,
+            cutout_pipeline,
+        }
+    }
+
+    /// Fits the sun's view travelling along `sun_direction` around
+    /// `scene_bounds` and uploads it.
+    pub fn update(
+        &self,
+        queue: &egui_wgpu::wgpu::Queue,
+        sun_direction: cgmath::Vector3<f32>,
+        scene_bounds: &bounds::Aabb,
+    ) {
+        let direction = sun_direction.normalize();
+        let center = scene_bounds.center();
+        let radius = scene_bounds.half_extents().magnitude().max(0.01);
+        let eye = center - direction * radius;
+        // Any up vector works as long as it isn't parallel to the sunlight
+        let up = if direction.y.abs() > 0.99 {
+            cgmath::Vector3::unit_z()
+        } else {
+            cgmath::Vector3::unit_y()
+        };
+        let view = cgmath::Matrix4::look_to_rh(eye, direction, up);
+        // Orthographic projection of the sphere around the bounds, mapping
+        // depth straight to 0..1
+        #[rustfmt::skip]
+        let projection = cgmath::Matrix4::new(
+            1.0 / radius, 0.0, 0.0, 0.0,
+            0.0, 1.0 / radius, 0.0, 0.0,
+            0.0, 0.0, -0.5 / radius, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        let light_view_proj = projection * view;
+
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[state::CameraUniform::from_view_proj(light_view_proj, eye)]),
+        );
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[ShadowParams {
+                light_view_proj: light_view_proj.into(),
+                bias: self.bias,
+                enabled: self.enabled as u32,
+                texel_size: 1.0 / SHADOW_MAP_SIZE as f32,
+                _padding: 0,
+            }]),
+        );
+    }
+
+    /// Starts the pass rendering the shadow casters, alpha tested if `cutout`
+    /// is set. The scene's texture bind group still needs to be set at group 0
+    /// before drawing.
+    pub fn begin_pass<'a>(
+        &'a self,
+        encoder: &'a mut egui_wgpu::wgpu::CommandEncoder,
+        cutout: bool,
+    ) -> egui_wgpu::wgpu::RenderPass<'a> {
+        let mut render_pass = encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(egui_wgpu::wgpu::RenderPassDepthStencilAttachment {
+                view: &self.texture.view,
+                depth_ops: Some(egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Clear(1.0),
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(if cutout {
+            &self.cutout_pipeline
+        } else {
+            &self.opaque_pipeline
+        });
+        render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+        render_pass
+    }
+}
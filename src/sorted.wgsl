@@ -0,0 +1,30 @@
+// Sorted transparency composite
+//
+// `t_layer` holds the transparent surfaces blended back to front over a
+// cleared target, as premultiplied color and coverage.
+
+@group(0) @binding(0)
+var t_layer: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// A single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+// Blended over the opaque scene as premultiplied alpha
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let layer = textureLoad(t_layer, vec2<i32>(in.clip_position.xy), 0);
+    if layer.a <= 0.0 {
+        discard;
+    }
+    return layer;
+}
@@ -0,0 +1,439 @@
+use crate::{camera, hdr, postprocess, sampling, texture};
+use cgmath::prelude::*;
+use egui_wgpu::wgpu::util::DeviceExt;
+
+/// Upper bound for `Ssao::kernel_size`, the size of the kernel in the shader.
+pub const MAX_KERNEL_SIZE: usize = 32;
+const AO_FORMAT: egui_wgpu::wgpu::TextureFormat = egui_wgpu::wgpu::TextureFormat::R8Unorm;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SsaoUniform {
+    proj: [[f32; 4]; 4],
+    inv_proj: [[f32; 4]; 4],
+    kernel: [[f32; 4]; MAX_KERNEL_SIZE],
+    radius: f32,
+    intensity: f32,
+    bias: f32,
+    kernel_size: u32,
+    far_depth: f32,
+    zfar: f32,
+    _padding: [u32; 2],
+}
+
+/// Screen space ambient occlusion from the scene's depth buffer. Normals are
+/// reconstructed from depth, so no extra geometry pass is needed. The result
+/// is blurred and multiplied into the HDR scene.
+pub struct Ssao {
+    pub enabled: bool,
+    /// Replace the scene with the occlusion as it is before blurring.
+    pub debug_view: bool,
+    /// Replace the scene with its distance from the camera, black up close
+    /// and white from `Projection::zfar` on. Shown even with SSAO disabled.
+    pub depth_view: bool,
+    /// Size of the sampled hemisphere in world units.
+    pub radius: f32,
+    /// Exponent applied to the occlusion, higher is darker.
+    pub intensity: f32,
+    /// Depth difference ignored to avoid surfaces occluding themselves.
+    pub bias: f32,
+    pub kernel_size: u32,
+    kernel: [[f32; 4]; MAX_KERNEL_SIZE],
+    uniform_buffer: egui_wgpu::wgpu::Buffer,
+    uniform_bind_group: egui_wgpu::wgpu::BindGroup,
+    uniform_layout: egui_wgpu::wgpu::BindGroupLayout,
+    ao_layout: egui_wgpu::wgpu::BindGroupLayout,
+    depth_layout: egui_wgpu::wgpu::BindGroupLayout,
+    depth_bind_group: egui_wgpu::wgpu::BindGroup,
+    sample_count: u32,
+    ssao_pipeline: egui_wgpu::wgpu::RenderPipeline,
+    depth_pipeline: egui_wgpu::wgpu::RenderPipeline,
+    blur_pipeline: egui_wgpu::wgpu::RenderPipeline,
+    composite_pipeline: egui_wgpu::wgpu::RenderPipeline,
+    debug_pipeline: egui_wgpu::wgpu::RenderPipeline,
+    raw: texture::Texture,
+    raw_bind_group: egui_wgpu::wgpu::BindGroup,
+    blurred: texture::Texture,
+    blurred_bind_group: egui_wgpu::wgpu::BindGroup,
+}
+
+impl Ssao {
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        config: &egui_wgpu::wgpu::SurfaceConfiguration,
+        depth_texture: &texture::Texture,
+        sample_count: u32,
+    ) -> Self {
+        let uniform_layout =
+            device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+                entries: &[egui_wgpu::wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+                    ty: egui_wgpu::wgpu::BindingType::Buffer {
+                        ty: egui_wgpu::wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("ssao_uniform_bind_group_layout"),
+            });
+        let ao_layout =
+            device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+                entries: &[egui_wgpu::wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+                    ty: egui_wgpu::wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: egui_wgpu::wgpu::TextureViewDimension::D2,
+                        sample_type: egui_wgpu::wgpu::TextureSampleType::Float {
+                            filterable: false,
+                        },
+                    },
+                    count: None,
+                }],
+                label: Some("ssao_input_bind_group_layout"),
+            });
+        let depth_layout = create_depth_layout(device, sample_count);
+
+        let kernel = create_kernel();
+        let uniform_buffer =
+            device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
+                label: Some("SSAO Buffer"),
+                contents: bytemuck::cast_slice(&[SsaoUniform {
+                    proj: cgmath::Matrix4::identity().into(),
+                    inv_proj: cgmath::Matrix4::identity().into(),
+                    kernel,
+                    radius: 0.0,
+                    intensity: 0.0,
+                    bias: 0.0,
+                    kernel_size: 0,
+                    far_depth: 1.0,
+                    zfar: 1.0,
+                    _padding: [0; 2],
+                }]),
+                usage: egui_wgpu::wgpu::BufferUsages::UNIFORM
+                    | egui_wgpu::wgpu::BufferUsages::COPY_DST,
+            });
+        let uniform_bind_group = device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+            layout: &uniform_layout,
+            entries: &[egui_wgpu::wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("ssao_uniform_bind_group"),
+        });
+
+        let blur_shader =
+            device.create_shader_module(egui_wgpu::wgpu::include_wgsl!("ssao_blur.wgsl"));
+        let blur_layout =
+            device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+                label: Some("SSAO Blur Pipeline Layout"),
+                bind_group_layouts: &[&ao_layout],
+                push_constant_ranges: &[],
+            });
+        let multiply = egui_wgpu::wgpu::BlendState {
+            color: egui_wgpu::wgpu::BlendComponent {
+                src_factor: egui_wgpu::wgpu::BlendFactor::Zero,
+                dst_factor: egui_wgpu::wgpu::BlendFactor::Src,
+                operation: egui_wgpu::wgpu::BlendOperation::Add,
+            },
+            alpha: egui_wgpu::wgpu::BlendComponent::REPLACE,
+        };
+        let blur_pipeline = postprocess::create_pipeline(
+            device,
+            &blur_layout,
+            &blur_shader,
+            "fs_blur",
+            AO_FORMAT,
+            None,
+        );
+        let composite_pipeline = postprocess::create_pipeline(
+            device,
+            &blur_layout,
+            &blur_shader,
+            "fs_composite",
+            hdr::HDR_FORMAT,
+            Some(multiply),
+        );
+        let debug_pipeline = postprocess::create_pipeline(
+            device,
+            &blur_layout,
+            &blur_shader,
+            "fs_composite",
+            hdr::HDR_FORMAT,
+            None,
+        );
+
+        let ssao_pipeline = create_ssao_pipeline(
+            device,
+            &depth_layout,
+            &uniform_layout,
+            "fs_ssao",
+            sample_count,
+        );
+        let depth_pipeline = create_ssao_pipeline(
+            device,
+            &depth_layout,
+            &uniform_layout,
+            "fs_depth",
+            sample_count,
+        );
+        let depth_bind_group = create_depth_bind_group(device, &depth_layout, depth_texture);
+        let (raw, raw_bind_group) = create_target(device, &ao_layout, config, "ssao_texture");
+        let (blurred, blurred_bind_group) =
+            create_target(device, &ao_layout, config, "ssao_blurred_texture");
+
+        Self {
+            enabled: true,
+            debug_view: false,
+            depth_view: false,
+            radius: 0.5,
+            intensity: 1.5,
+            bias: 0.025,
+            kernel_size: 16,
+            kernel,
+            uniform_buffer,
+            uniform_bind_group,
+            uniform_layout,
+            ao_layout,
+            depth_layout,
+            depth_bind_group,
+            sample_count,
+            ssao_pipeline,
+            depth_pipeline,
+            blur_pipeline,
+            composite_pipeline,
+            debug_pipeline,
+            raw,
+            raw_bind_group,
+            blurred,
+            blurred_bind_group,
+        }
+    }
+
+    /// Picks up a recreated depth texture, rebuilding the pipeline reading it
+    /// if its sample count changed.
+    pub fn resize(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        config: &egui_wgpu::wgpu::SurfaceConfiguration,
+        depth_texture: &texture::Texture,
+        sample_count: u32,
+    ) {
+        if sample_count != self.sample_count {
+            self.sample_count = sample_count;
+            self.depth_layout = create_depth_layout(device, sample_count);
+            self.ssao_pipeline = create_ssao_pipeline(
+                device,
+                &self.depth_layout,
+                &self.uniform_layout,
+                "fs_ssao",
+                sample_count,
+            );
+            self.depth_pipeline = create_ssao_pipeline(
+                device,
+                &self.depth_layout,
+                &self.uniform_layout,
+                "fs_depth",
+                sample_count,
+            );
+        }
+        self.depth_bind_group = create_depth_bind_group(device, &self.depth_layout, depth_texture);
+        (self.raw, self.raw_bind_group) =
+            create_target(device, &self.ao_layout, config, "ssao_texture");
+        (self.blurred, self.blurred_bind_group) =
+            create_target(device, &self.ao_layout, config, "ssao_blurred_texture");
+    }
+
+    /// Computes the occlusion for the depth buffer of the frame and applies it
+    /// to `output`, the resolved HDR scene.
+    pub fn render(
+        &self,
+        queue: &egui_wgpu::wgpu::Queue,
+        encoder: &mut egui_wgpu::wgpu::CommandEncoder,
+        projection: &dyn camera::Projection,
+        output: &egui_wgpu::wgpu::TextureView,
+    ) {
+        if !self.enabled && !self.depth_view {
+            return;
+        }
+
+        let proj = projection.calc_matrix();
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[SsaoUniform {
+                proj: proj.into(),
+                inv_proj: proj.invert().unwrap_or(cgmath::Matrix4::identity()).into(),
+                kernel: self.kernel,
+                radius: self.radius,
+                intensity: self.intensity,
+                bias: self.bias,
+                kernel_size: self.kernel_size.clamp(1, MAX_KERNEL_SIZE as u32),
+                far_depth: projection.depth_mode().far_depth(),
+                zfar: projection.zfar(),
+                _padding: [0; 2],
+            }]),
+        );
+
+        if self.depth_view {
+            postprocess::draw(
+                encoder,
+                "Depth View Pass",
+                &self.depth_pipeline,
+                &[&self.depth_bind_group, &self.uniform_bind_group],
+                &self.raw.view,
+                egui_wgpu::wgpu::LoadOp::Clear(egui_wgpu::wgpu::Color::WHITE),
+            );
+            postprocess::draw(
+                encoder,
+                "Depth View Composite Pass",
+                &self.debug_pipeline,
+                &[&self.raw_bind_group],
+                output,
+                egui_wgpu::wgpu::LoadOp::Load,
+            );
+            return;
+        }
+
+        postprocess::draw(
+            encoder,
+            "SSAO Pass",
+            &self.ssao_pipeline,
+            &[&self.depth_bind_group, &self.uniform_bind_group],
+            &self.raw.view,
+            egui_wgpu::wgpu::LoadOp::Clear(egui_wgpu::wgpu::Color::WHITE),
+        );
+        postprocess::draw(
+            encoder,
+            "SSAO Blur Pass",
+            &self.blur_pipeline,
+            &[&self.raw_bind_group],
+            &self.blurred.view,
+            egui_wgpu::wgpu::LoadOp::Clear(egui_wgpu::wgpu::Color::WHITE),
+        );
+        let (pipeline, input) = if self.debug_view {
+            (&self.debug_pipeline, &self.raw_bind_group)
+        } else {
+            (&self.composite_pipeline, &self.blurred_bind_group)
+        };
+        postprocess::draw(
+            encoder,
+            "SSAO Composite Pass",
+            pipeline,
+            &[input],
+            output,
+            egui_wgpu::wgpu::LoadOp::Load,
+        );
+    }
+}
+
+/// Hemisphere samples around +Z, spread with a Halton sequence and packed
+/// more densely towards the center where occlusion matters most.
+fn create_kernel() -> [[f32; 4]; MAX_KERNEL_SIZE] {
+    std::array::from_fn(|i| {
+        let phi = sampling::halton(i + 1, 2) * std::f32::consts::TAU;
+        let cos_theta = sampling::halton(i + 1, 3);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let length = sampling::halton(i + 1, 5).max(0.1);
+        let scale = i as f32 / MAX_KERNEL_SIZE as f32;
+        let scale = 0.1 + 0.9 * scale * scale;
+        let v = cgmath::Vector3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta)
+            * length
+            * scale;
+        [v.x, v.y, v.z, 0.0]
+    })
+}
+
+fn create_depth_layout(
+    device: &egui_wgpu::wgpu::Device,
+    sample_count: u32,
+) -> egui_wgpu::wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+        entries: &[egui_wgpu::wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+            ty: egui_wgpu::wgpu::BindingType::Texture {
+                multisampled: sample_count > 1,
+                view_dimension: egui_wgpu::wgpu::TextureViewDimension::D2,
+                // Read as plain floats, loading from depth textures isn't
+                // supported on GL
+                sample_type: egui_wgpu::wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        }],
+        label: Some("ssao_depth_bind_group_layout"),
+    })
+}
+
+fn create_depth_bind_group(
+    device: &egui_wgpu::wgpu::Device,
+    layout: &egui_wgpu::wgpu::BindGroupLayout,
+    depth_texture: &texture::Texture,
+) -> egui_wgpu::wgpu::BindGroup {
+    device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[egui_wgpu::wgpu::BindGroupEntry {
+            binding: 0,
+            resource: egui_wgpu::wgpu::BindingResource::TextureView(&depth_texture.view),
+        }],
+        label: Some("ssao_depth_bind_group"),
+    })
+}
+
+/// The occlusion pass, with the depth texture declared to match the sample
+/// count it was created with.
+fn create_ssao_pipeline(
+    device: &egui_wgpu::wgpu::Device,
+    depth_layout: &egui_wgpu::wgpu::BindGroupLayout,
+    uniform_layout: &egui_wgpu::wgpu::BindGroupLayout,
+    entry_point: &str,
+    sample_count: u32,
+) -> egui_wgpu::wgpu::RenderPipeline {
+    let depth_type = if sample_count > 1 {
+        "texture_multisampled_2d<f32>"
+    } else {
+        "texture_2d<f32>"
+    };
+    let source = format!(
+        "@group(0) @binding(0)\nvar t_depth: {};\n\n\
+         fn load_depth(coord: vec2<i32>) -> f32 {{\n    return textureLoad(t_depth, coord, 0).r;\n}}\n\n{}",
+        depth_type,
+        include_str!("ssao.wgsl")
+    );
+    let shader = device.create_shader_module(egui_wgpu::wgpu::ShaderModuleDescriptor {
+        label: Some("ssao.wgsl"),
+        source: egui_wgpu::wgpu::ShaderSource::Wgsl(source.into()),
+    });
+    let layout = device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+        label: Some("SSAO Pipeline Layout"),
+        bind_group_layouts: &[depth_layout, uniform_layout],
+        push_constant_ranges: &[],
+    });
+    postprocess::create_pipeline(device, &layout, &shader, entry_point, AO_FORMAT, None)
+}
+
+fn create_target(
+    device: &egui_wgpu::wgpu::Device,
+    layout: &egui_wgpu::wgpu::BindGroupLayout,
+    config: &egui_wgpu::wgpu::SurfaceConfiguration,
+    label: &str,
+) -> (texture::Texture, egui_wgpu::wgpu::BindGroup) {
+    let texture = texture::Texture::create_render_target(
+        device,
+        config.width,
+        config.height,
+        AO_FORMAT,
+        label,
+    );
+    let bind_group = device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[egui_wgpu::wgpu::BindGroupEntry {
+            binding: 0,
+            resource: egui_wgpu::wgpu::BindingResource::TextureView(&texture.view),
+        }],
+        label: Some(label),
+    });
+    (texture, bind_group)
+}
@@ -0,0 +1,118 @@
+// Screen space ambient occlusion
+//
+// `t_depth` and `load_depth` are prepended by `Ssao`, as the depth texture
+// is multisampled when MSAA is enabled. Sample 0 stands in for the pixel.
+
+const MAX_KERNEL_SIZE: u32 = 32u;
+
+struct Ssao {
+    proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    kernel: array<vec4<f32>, MAX_KERNEL_SIZE>,
+    radius: f32,
+    intensity: f32,
+    bias: f32,
+    kernel_size: u32,
+    // Depth buffer value of the far plane, left by the clear
+    far_depth: f32,
+    zfar: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> ssao: Ssao;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// A single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+fn view_position(coord: vec2<i32>) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(t_depth));
+    let uv = (vec2<f32>(coord) + 0.5) / size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, load_depth(coord), 1.0);
+    let position = ssao.inv_proj * ndc;
+    return position.xyz / position.w;
+}
+
+// Picks the neighbour on the same surface on each axis, so that normals
+// don't bend around depth discontinuities.
+fn view_normal(coord: vec2<i32>, center: vec3<f32>) -> vec3<f32> {
+    let left = view_position(coord - vec2<i32>(1, 0));
+    let right = view_position(coord + vec2<i32>(1, 0));
+    let up = view_position(coord - vec2<i32>(0, 1));
+    let down = view_position(coord + vec2<i32>(0, 1));
+
+    var dx = right - center;
+    if abs(center.z - left.z) < abs(right.z - center.z) {
+        dx = center - left;
+    }
+    var dy = center - up;
+    if abs(down.z - center.z) < abs(center.z - up.z) {
+        dy = down - center;
+    }
+    return normalize(cross(dy, dx));
+}
+
+// Rotation of the kernel around the normal, repeating every 4x4 pixels so
+// that the blur pass can average the noise away
+fn random_vector(coord: vec2<i32>) -> vec3<f32> {
+    let cell = vec2<u32>(coord) % 4u;
+    let angle = f32(cell.x * 4u + cell.y) * 2.39996323;
+    return vec3<f32>(cos(angle), sin(angle), 0.0);
+}
+
+@fragment
+fn fs_ssao(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.clip_position.xy);
+    if load_depth(coord) == ssao.far_depth {
+        return vec4<f32>(1.0);
+    }
+
+    let position = view_position(coord);
+    let normal = view_normal(coord, position);
+    let random = random_vector(coord);
+    let tangent = normalize(random - normal * dot(random, normal));
+    let bitangent = cross(normal, tangent);
+    let tbn = mat3x3<f32>(tangent, bitangent, normal);
+
+    let size = vec2<f32>(textureDimensions(t_depth));
+    let max_coord = vec2<i32>(size) - 1;
+    var occlusion = 0.0;
+    for (var i = 0u; i < ssao.kernel_size; i++) {
+        let sample = position + tbn * ssao.kernel[i].xyz * ssao.radius;
+
+        let clip = ssao.proj * vec4<f32>(sample, 1.0);
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        let sample_coord = clamp(vec2<i32>(uv * size), vec2<i32>(0), max_coord);
+        let scene_z = view_position(sample_coord).z;
+
+        // Geometry far in front of the sample shouldn't darken it
+        let range = smoothstep(0.0, 1.0, ssao.radius / abs(position.z - scene_z));
+        if scene_z >= sample.z + ssao.bias {
+            occlusion += range;
+        }
+    }
+
+    let ao = 1.0 - occlusion / f32(ssao.kernel_size);
+    return vec4<f32>(pow(ao, ssao.intensity));
+}
+
+// Distance from the camera, black up close and white from `zfar` on, for
+// looking at the depth buffer itself
+@fragment
+fn fs_depth(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.clip_position.xy);
+    if load_depth(coord) == ssao.far_depth {
+        return vec4<f32>(1.0);
+    }
+    return vec4<f32>(-view_position(coord).z / ssao.zfar);
+}
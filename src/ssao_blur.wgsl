@@ -0,0 +1,40 @@
+// Blurs the raw ambient occlusion and applies it to the scene
+
+@group(0) @binding(0)
+var t_ao: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// A single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+// Averages a 4x4 block, the size of the pattern the kernel is rotated with
+@fragment
+fn fs_blur(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.clip_position.xy);
+    let max_coord = vec2<i32>(textureDimensions(t_ao)) - 1;
+    var ao = 0.0;
+    for (var x = -2; x < 2; x++) {
+        for (var y = -2; y < 2; y++) {
+            let offset = clamp(coord + vec2<i32>(x, y), vec2<i32>(0), max_coord);
+            ao += textureLoad(t_ao, offset, 0).r;
+        }
+    }
+    return vec4<f32>(ao / 16.0);
+}
+
+// Multiplied with the scene through blending, or written as is for the debug
+// view
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let ao = textureLoad(t_ao, vec2<i32>(in.clip_position.xy), 0).r;
+    return vec4<f32>(vec3<f32>(ao), 1.0);
+}
@@ -1,74 +1,29 @@
-use crate::{camera, gui, texture};
+use crate::{
+    bookmark, bounds, camera, camera_path, cluster, culling, deferred, gui, hdr, highlight, input,
+    instance, light, lod, mesh, postprocess, screenshot, settings, shadow, ssao, texture,
+    transparency,
+};
 use cgmath::prelude::*;
 use egui_wgpu::wgpu::util::DeviceExt;
-use egui_winit::winit::{event::*, keyboard::PhysicalKey, window::Window};
-use tracing::{debug, debug_span, error, trace};
+use egui_winit::winit::{
+    event::*,
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
+    window::{CursorGrabMode, Window},
+};
+use tracing::{debug, debug_span, error, info, trace, warn};
 
-const NUM_INSTANCES_PER_ROW: u32 = 15;
-const INSTANCE_DISPLACEMENT: cgmath::Vector3<f32> = cgmath::Vector3::new(
-    NUM_INSTANCES_PER_ROW as f32 * 0.5,
-    NUM_INSTANCES_PER_ROW as f32 * 0.5,
-    NUM_INSTANCES_PER_ROW as f32 * 0.5,
-);
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    tex_coords: [f32; 2],
-}
-
-impl Vertex {
-    fn desc() -> egui_wgpu::wgpu::VertexBufferLayout<'static> {
-        use std::mem;
-        egui_wgpu::wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Vertex>() as egui_wgpu::wgpu::BufferAddress,
-            step_mode: egui_wgpu::wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                egui_wgpu::wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: egui_wgpu::wgpu::VertexFormat::Float32x3,
-                },
-                egui_wgpu::wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 3]>() as egui_wgpu::wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: egui_wgpu::wgpu::VertexFormat::Float32x2,
-                },
-            ],
-        }
-    }
-}
+#[cfg(feature = "gamepad")]
+use crate::gamepad;
 
-const VERTICES: &[Vertex] = &[
-    Vertex {
-        position: [-0.5, 0.5, 0.0],
-        tex_coords: [0.0, 0.0],
-    },
-    Vertex {
-        position: [0.5, 0.5, 0.0],
-        tex_coords: [1.0, 0.0],
-    },
-    Vertex {
-        position: [-0.5, -0.5, 0.0],
-        tex_coords: [0.0, 1.0],
-    },
-    Vertex {
-        position: [0.5, -0.5, 0.0],
-        tex_coords: [1.0, 1.0],
-    },
-];
-
-#[rustfmt::skip]
-const INDICES: &[u16] = &[
-    1, 0, 2,
-    1, 2, 3
-];
+const NUM_INSTANCES_PER_ROW: u32 = 15;
+const NUM_POINT_LIGHTS: usize = 64;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct CameraUniform {
+pub(crate) struct CameraUniform {
     view_proj: [[f32; 4]; 4],
+    inv_view_proj: [[f32; 4]; 4],
+    position: [f32; 4],
 }
 
 impl CameraUniform {
@@ -77,64 +32,38 @@ impl CameraUniform {
 
         Self {
             view_proj: cgmath::Matrix4::identity().into(),
+            inv_view_proj: cgmath::Matrix4::identity().into(),
+            position: [0.0; 4],
         }
     }
 
-    fn update_view_proj(&mut self, camera: &camera::Camera, projection: &camera::Projection) {
-        self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
-    }
-}
-
-struct Instance {
-    position: cgmath::Vector3<f32>,
-    rotation: cgmath::Quaternion<f32>,
-}
-
-impl Instance {
-    fn to_raw(&self) -> InstanceRaw {
-        InstanceRaw {
-            model: (cgmath::Matrix4::from_translation(self.position)
-                * cgmath::Matrix4::from(self.rotation))
-            .into(),
+    /// Uniform for a view from `position` through `view_proj`, which needn't
+    /// be the camera's.
+    pub(crate) fn from_view_proj(
+        view_proj: cgmath::Matrix4<f32>,
+        position: cgmath::Point3<f32>,
+    ) -> Self {
+        Self {
+            view_proj: view_proj.into(),
+            inv_view_proj: view_proj
+                .invert()
+                .unwrap_or(cgmath::Matrix4::identity())
+                .into(),
+            position: position.to_homogeneous().into(),
         }
     }
-}
 
-#[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct InstanceRaw {
-    model: [[f32; 4]; 4],
-}
+    fn update_view_proj(&mut self, camera: &camera::Camera, projection: &dyn camera::Projection) {
+        *self = Self::from_view_proj(
+            projection.calc_matrix() * camera.calc_matrix(),
+            camera.position,
+        );
+    }
 
-impl InstanceRaw {
-    fn desc() -> egui_wgpu::wgpu::VertexBufferLayout<'static> {
-        use std::mem;
-        egui_wgpu::wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<InstanceRaw>() as egui_wgpu::wgpu::BufferAddress,
-            step_mode: egui_wgpu::wgpu::VertexStepMode::Instance,
-            attributes: &[
-                egui_wgpu::wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 5,
-                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
-                },
-                egui_wgpu::wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 4]>() as egui_wgpu::wgpu::BufferAddress,
-                    shader_location: 6,
-                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
-                },
-                egui_wgpu::wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 8]>() as egui_wgpu::wgpu::BufferAddress,
-                    shader_location: 7,
-                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
-                },
-                egui_wgpu::wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 12]>() as egui_wgpu::wgpu::BufferAddress,
-                    shader_location: 8,
-                    format: egui_wgpu::wgpu::VertexFormat::Float32x4,
-                },
-            ],
-        }
+    /// Culling planes of the view, which only the CPU needs, so they are
+    /// derived here rather than uploaded with the rest.
+    fn frustum(&self) -> camera::Frustum {
+        camera::Frustum::from_matrix(self.view_proj.into())
     }
 }
 
@@ -143,6 +72,7 @@ pub struct Status {
     pub fps_avg: f32,
     pub delta: u128,
     pub cap_frame_rate: bool,
+    pub culled: usize,
 }
 
 impl Default for Status {
@@ -152,6 +82,7 @@ impl Default for Status {
             fps_avg: 0.0,
             delta: 0,
             cap_frame_rate: true,
+            culled: 0,
         }
     }
 }
@@ -162,25 +93,96 @@ pub struct State<'a> {
     pub window: &'a Window,
     pub status: Status,
     pub mouse_pressed: bool,
+    /// Cursor hidden and held in place, with mouse movement always turning
+    /// the fly camera.
+    cursor_grabbed: bool,
+    /// Whether egui wanted the pointer last frame, to let go of a grabbed
+    /// cursor as it moves onto the UI.
+    pointer_over_ui: bool,
+    /// Last position of the cursor over the window, for picking.
+    cursor_position: Option<egui_winit::winit::dpi::PhysicalPosition<f64>>,
     clear_color: egui_wgpu::wgpu::Color,
     surface: egui_wgpu::wgpu::Surface<'a>,
     device: egui_wgpu::wgpu::Device,
     queue: egui_wgpu::wgpu::Queue,
     config: egui_wgpu::wgpu::SurfaceConfiguration,
-    render_pipeline: egui_wgpu::wgpu::RenderPipeline,
-    vertex_buffer: egui_wgpu::wgpu::Buffer,
-    index_buffer: egui_wgpu::wgpu::Buffer,
-    num_indices: u32,
+    render_pipeline_layout: egui_wgpu::wgpu::PipelineLayout,
+    shader: egui_wgpu::wgpu::ShaderModule,
+    forward_pipelines: ForwardPipelines,
+    supported_sample_counts: Vec<u32>,
+    sample_count: u32,
+    msaa_view: Option<egui_wgpu::wgpu::TextureView>,
+    hdr: hdr::HdrPipeline,
+    post_process: postprocess::PostProcess,
+    ssao: ssao::Ssao,
+    render_path: deferred::RenderPath,
+    deferred: deferred::DeferredRenderer,
+    lights: light::Lights,
+    light_clusters: cluster::LightClusters,
+    alpha_mode: transparency::AlphaMode,
+    blend_mode: transparency::BlendMode,
+    sorted: transparency::SortedBlend,
+    oit: transparency::WeightedBlendedOit,
+    lod: lod::LodMesh,
+    /// Draws a subdivided quad with simplified levels instead of the plain
+    /// one, which is too simple to have any.
+    subdivided_mesh: bool,
+    /// Range of the instance buffer drawn with each level of detail.
+    lod_ranges: Vec<std::ops::Range<u32>>,
+    /// Range of `transparent_buffer` drawn with each level of detail.
+    transparent_ranges: Vec<std::ops::Range<u32>>,
     diffuse_bind_group: egui_wgpu::wgpu::BindGroup,
     _diffuse_texture: texture::Texture,
     camera: camera::Camera,
-    projection: camera::Projection,
+    projection: camera::Projections,
     pub camera_controller: camera::CameraController,
+    pub orbit_controller: camera::OrbitController,
+    pub controller_kind: camera::ControllerKind,
+    /// None if gamepads couldn't be set up on this system.
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<gamepad::Gamepads>,
+    /// Takes over the camera from the controllers while playing.
+    camera_path: camera_path::CameraPath,
+    camera_path_file: String,
+    bookmarks: bookmark::Bookmarks,
+    /// Name for the next bookmark added from the Debug window.
+    bookmark_name: String,
+    /// Held modifiers, for the Ctrl variants of the axis views.
+    modifiers: ModifiersState,
+    input_map: input::InputMap,
+    /// Settings saved whenever they change.
+    settings: settings::Settings,
+    /// Action and slot waiting for a key press to bind.
+    rebinding: Option<(input::Action, usize)>,
+    /// Saves the next frame, taken before egui draws on top.
+    screenshot_requested: bool,
     camera_uniform: CameraUniform,
     camera_buffer: egui_wgpu::wgpu::Buffer,
     camera_bind_group: egui_wgpu::wgpu::BindGroup,
-    instances: Vec<Instance>,
-    instance_buffer: egui_wgpu::wgpu::Buffer,
+    instances: Vec<instance::Instance>,
+    instance_buffer: instance::InstanceBuffer,
+    /// Set when `instances` change, until `update` has caught up.
+    instances_dirty: bool,
+    /// Whether `instance_buffer` holds every opaque instance, rather than
+    /// this frame's culled selection.
+    instance_buffer_full: bool,
+    /// Indices of the transparent instances, which are left out of
+    /// `instance_buffer` and drawn after the opaque ones.
+    transparent_instances: Vec<usize>,
+    /// This frame's culled transparent instances, sorted back to front for
+    /// `BlendMode::Sorted`.
+    transparent_buffer: instance::InstanceBuffer,
+    /// Every opaque instance for the shadow pass, while `instance_buffer`
+    /// only holds the ones in view. Only uploaded once it is needed.
+    caster_buffer: instance::InstanceBuffer,
+    caster_buffer_current: bool,
+    /// Bounds of all instances, which the shadow map covers.
+    scene_bounds: bounds::Aabb,
+    instance_editor: gui::InstanceEditor,
+    /// Outlines the instance selected in `instance_editor`.
+    highlight: highlight::Highlight,
+    culler: culling::GpuCuller,
+    culling_mode: culling::CullingMode,
     depth_texture: texture::Texture,
 }
 
@@ -233,7 +235,9 @@ impl<'a> State<'a> {
         let (device, queue) = match adapter
             .request_device(
                 &egui_wgpu::wgpu::DeviceDescriptor {
-                    required_features: egui_wgpu::wgpu::Features::empty(),
+                    // Without this only 1x and 4x MSAA are guaranteed to work
+                    required_features: adapter.features()
+                        & egui_wgpu::wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
                     required_limits: egui_wgpu::wgpu::Limits::default(),
                     label: None,
                     // memory_hints: Default::default(),
@@ -259,7 +263,9 @@ impl<'a> State<'a> {
             .copied()
             .unwrap_or(surface_caps.formats[0]);
         let config = egui_wgpu::wgpu::SurfaceConfiguration {
-            usage: egui_wgpu::wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // Copying out of the surface is only needed for screenshots
+            usage: egui_wgpu::wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_caps.usages & egui_wgpu::wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             width: size.width,
             height: size.height,
@@ -270,14 +276,46 @@ impl<'a> State<'a> {
         };
         trace!("Surface configuration created: {:?}", config);
 
+        let supported_sample_counts = supported_sample_counts(&adapter, &device, hdr::HDR_FORMAT);
+        let sample_count = if supported_sample_counts.contains(&4) {
+            4
+        } else {
+            1
+        };
+        debug!(
+            "Supported MSAA sample counts: {:?}, using {}",
+            supported_sample_counts, sample_count
+        );
+
         surface.configure(&device, &config);
         let diffuse_bytes = include_bytes!("happy-tree.png");
-        let diffuse_texture =
-            texture::Texture::from_bytes(&device, &queue, diffuse_bytes, "happy-tree.png").unwrap();
+        let diffuse_image = image::load_from_memory(diffuse_bytes).unwrap();
+        let diffuse_texture = texture::Texture::from_layers(
+            &device,
+            &queue,
+            &[diffuse_image],
+            Some("happy-tree.png"),
+        )
+        .unwrap();
         trace!("Diffuse texture created");
 
         let depth_texture =
-            texture::Texture::create_depth_texture(&device, &config, "depth_texture");
+            texture::Texture::create_depth_texture(&device, &config, sample_count, "depth_texture");
+        let msaa_view = (sample_count > 1).then(|| {
+            texture::Texture::create_msaa_texture(
+                &device,
+                &config,
+                hdr::HDR_FORMAT,
+                sample_count,
+                "msaa_texture",
+            )
+        });
+        let hdr = hdr::HdrPipeline::new(&device, &config);
+        trace!("HDR pipeline created");
+        let post_process = postprocess::PostProcess::new(&device, &config, &hdr);
+        trace!("Post processing created");
+        let ssao = ssao::Ssao::new(&device, &config, &depth_texture, sample_count);
+        trace!("SSAO created");
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
@@ -287,7 +325,7 @@ impl<'a> State<'a> {
                         visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
                         ty: egui_wgpu::wgpu::BindingType::Texture {
                             multisampled: false,
-                            view_dimension: egui_wgpu::wgpu::TextureViewDimension::D2,
+                            view_dimension: egui_wgpu::wgpu::TextureViewDimension::D2Array,
                             sample_type: egui_wgpu::wgpu::TextureSampleType::Float {
                                 filterable: true,
                             },
@@ -323,54 +361,71 @@ impl<'a> State<'a> {
         });
         debug!("Diffuse bind group created");
 
-        let instances = (0..NUM_INSTANCES_PER_ROW)
-            .flat_map(|z| {
-                (0..NUM_INSTANCES_PER_ROW).flat_map(move |x| {
-                    (0..NUM_INSTANCES_PER_ROW)
-                        .map(move |y| {
-                            let position = cgmath::Vector3 {
-                                x: x as f32,
-                                y: y as f32,
-                                z: z as f32,
-                            } - INSTANCE_DISPLACEMENT;
-
-                            let rotation = if position.is_zero() {
-                                cgmath::Quaternion::from_axis_angle(
-                                    cgmath::Vector3::unit_z(),
-                                    cgmath::Deg(0.0),
-                                )
-                            } else {
-                                cgmath::Quaternion::from_axis_angle(
-                                    position.normalize(),
-                                    cgmath::Deg(30.0),
-                                )
-                            };
+        let instances = instance::grid(NUM_INSTANCES_PER_ROW);
+        let instance_buffer = instance::InstanceBuffer::new(&device, &queue, &instances);
+        trace!("Instance buffer created with {} instances", instances.len());
+        let caster_buffer = instance::InstanceBuffer::new(&device, &queue, &[]);
+        let transparent_buffer = instance::InstanceBuffer::new(&device, &queue, &[]);
 
-                            Instance { position, rotation }
-                        })
-                        .collect::<Vec<_>>()
-                })
-            })
-            .collect::<Vec<_>>();
-
-        let instance_data = instances
-            .iter()
-            .map(Instance::to_raw)
-            .collect::<Vec<InstanceRaw>>();
-        let instance_buffer =
-            device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
-                label: Some("Instance Buffer"),
-                contents: bytemuck::cast_slice(&instance_data),
-                usage: egui_wgpu::wgpu::BufferUsages::VERTEX,
-            });
+        let culler = culling::GpuCuller::new(&device, &instance_buffer);
+        let highlight = highlight::Highlight::new(&device, config.format);
+        trace!("GPU culler created");
 
         let camera = camera::Camera::new((0.0, 5.0, 20.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
-        let projection =
-            camera::Projection::new(config.width, config.height, cgmath::Deg(45.0), 0.1, 100.0);
-        let camera_controller = camera::CameraController::new(10.0, 1.0);
+        let settings = match settings::Settings::load(settings::SETTINGS_FILE) {
+            Ok(settings) => settings,
+            Err(e) => {
+                error!("{:#}", e);
+                set_aside(std::path::Path::new(settings::SETTINGS_FILE));
+                settings::Settings::new(settings::SETTINGS_FILE)
+            }
+        };
+        let mut projection = camera::Projections {
+            kind: camera::ProjectionKind::Perspective,
+            perspective: camera::Perspective::new(
+                config.width,
+                config.height,
+                cgmath::Deg(45.0),
+                0.1,
+                100.0,
+            ),
+            orthographic: camera::Orthographic::new(config.width, config.height, 20.0, 0.1, 100.0),
+        };
+        projection.set_depth_mode(settings.depth_mode);
+        // Turns the fly camera about a sixtieth of a radian per pixel, as fast
+        // as when mouse turns still scaled with frame time at 60 fps
+        let camera_controller = camera::CameraController::new(10.0, 3.3);
+        let orbit_controller = camera::OrbitController::new((0.0, 0.0, 0.0), 20.0, 1.0);
+        let input_map = match input::InputMap::load(input::BINDINGS_FILE) {
+            Ok(input_map) => input_map,
+            Err(e) => {
+                error!("{:#}", e);
+                set_aside(std::path::Path::new(input::BINDINGS_FILE));
+                input::InputMap::new(input::BINDINGS_FILE)
+            }
+        };
+        #[cfg(feature = "gamepad")]
+        let gamepads = match gamepad::Gamepads::new() {
+            Ok(gamepads) => Some(gamepads),
+            Err(e) => {
+                error!("Failed to set up gamepads: {:#}", e);
+                None
+            }
+        };
+        let bookmarks = match bookmark::Bookmarks::load(bookmark::BOOKMARKS_FILE) {
+            Ok(bookmarks) => {
+                trace!("Loaded {} bookmarks", bookmarks.bookmarks.len());
+                bookmarks
+            }
+            Err(e) => {
+                error!("{:#}", e);
+                set_aside(std::path::Path::new(bookmark::BOOKMARKS_FILE));
+                bookmark::Bookmarks::new(bookmark::BOOKMARKS_FILE)
+            }
+        };
 
         let mut camera_uniform = CameraUniform::new();
-        camera_uniform.update_view_proj(&camera, &projection);
+        camera_uniform.update_view_proj(&camera, projection.active());
         let camera_buffer =
             device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
                 label: Some("Camera Buffer"),
@@ -382,7 +437,9 @@ impl<'a> State<'a> {
             device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
                 entries: &[egui_wgpu::wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: egui_wgpu::wgpu::ShaderStages::VERTEX,
+                    // The fragment stage needs the camera position for lighting
+                    visibility: egui_wgpu::wgpu::ShaderStages::VERTEX
+                        | egui_wgpu::wgpu::ShaderStages::FRAGMENT,
                     ty: egui_wgpu::wgpu::BindingType::Buffer {
                         ty: egui_wgpu::wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -403,76 +460,76 @@ impl<'a> State<'a> {
         trace!("Camera created");
 
         trace!("Creating render pipeline");
-        let shader = device.create_shader_module(egui_wgpu::wgpu::include_wgsl!("shader.wgsl"));
+        let shader =
+            light::create_shader_module(&device, "shader.wgsl", include_str!("shader.wgsl"));
         debug!("Shader created");
+
+        let shadow = shadow::ShadowMap::new(
+            &device,
+            &texture_bind_group_layout,
+            &camera_bind_group_layout,
+            &shader,
+        );
+        let lights = light::Lights::new(
+            &device,
+            light::scatter(NUM_POINT_LIGHTS, NUM_INSTANCES_PER_ROW as f32),
+            shadow,
+        );
+        let light_clusters = cluster::LightClusters::new(&device, &lights.bind_group_layout);
+        trace!("Lights created");
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
+                bind_group_layouts: &[
+                    &texture_bind_group_layout,
+                    &camera_bind_group_layout,
+                    &lights.bind_group_layout,
+                    &light_clusters.bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
-        let render_pipeline =
-            device.create_render_pipeline(&egui_wgpu::wgpu::RenderPipelineDescriptor {
-                label: Some("Render Pipeline"),
-                layout: Some(&render_pipeline_layout),
-                vertex: egui_wgpu::wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &[Vertex::desc(), InstanceRaw::desc()],
-                    // compilation_options: egui_wgpu::wgpu::PipelineCompilationOptions::default(),
-                },
-                fragment: Some(egui_wgpu::wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(egui_wgpu::wgpu::ColorTargetState {
-                        format: config.format,
-                        blend: Some(egui_wgpu::wgpu::BlendState::REPLACE),
-                        write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
-                    })],
-                    // compilation_options: egui_wgpu::wgpu::PipelineCompilationOptions::default(),
-                }),
-                primitive: egui_wgpu::wgpu::PrimitiveState {
-                    topology: egui_wgpu::wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: egui_wgpu::wgpu::FrontFace::Ccw,
-                    cull_mode: Some(egui_wgpu::wgpu::Face::Back),
-                    // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                    polygon_mode: egui_wgpu::wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: Some(egui_wgpu::wgpu::DepthStencilState {
-                    format: texture::Texture::DEPTH_FORMAT,
-                    depth_write_enabled: true,
-                    depth_compare: egui_wgpu::wgpu::CompareFunction::Less,
-                    stencil: egui_wgpu::wgpu::StencilState::default(),
-                    bias: egui_wgpu::wgpu::DepthBiasState::default(),
-                }),
-                multisample: egui_wgpu::wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
-                // cache: None,
-            });
+        let forward_pipelines = ForwardPipelines::new(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            sample_count,
+            projection.depth_mode(),
+        );
         trace!("Render pipeline created");
+        let deferred = deferred::DeferredRenderer::new(
+            &device,
+            &config,
+            &render_pipeline_layout,
+            &shader,
+            &camera_bind_group_layout,
+            &lights.bind_group_layout,
+            &depth_texture,
+            sample_count,
+            projection.depth_mode(),
+        );
+        trace!("Deferred renderer created");
+        let sorted = transparency::SortedBlend::new(
+            &device,
+            &config,
+            &render_pipeline_layout,
+            &shader,
+            sample_count,
+            projection.depth_mode(),
+        );
+        let oit = transparency::WeightedBlendedOit::new(
+            &device,
+            &config,
+            &render_pipeline_layout,
+            &shader,
+            sample_count,
+            projection.depth_mode(),
+        );
+        trace!("Transparency targets created");
 
-        let vertex_buffer =
-            device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(VERTICES),
-                usage: egui_wgpu::wgpu::BufferUsages::VERTEX,
-            });
-        trace!("Vertex buffer created");
-        let index_buffer =
-            device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(INDICES),
-                usage: egui_wgpu::wgpu::BufferUsages::INDEX,
-            });
-        let num_indices = INDICES.len() as u32;
-        trace!("Index buffer created");
+        let lod = create_lod_mesh(&device, false);
+        let scene_bounds = instance_bounds(lod.base(), &instances);
+        trace!("Mesh created with {} levels of detail", lod.levels.len());
 
         let egui = gui::EguiRenderer::new(&device, window);
         trace!("Egui renderer created");
@@ -491,29 +548,106 @@ impl<'a> State<'a> {
             queue,
             config,
             window,
-            render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
+            render_pipeline_layout,
+            shader,
+            forward_pipelines,
+            supported_sample_counts,
+            sample_count,
+            msaa_view,
+            hdr,
+            post_process,
+            ssao,
+            render_path: deferred::RenderPath::Forward,
+            deferred,
+            lights,
+            light_clusters,
+            alpha_mode: transparency::AlphaMode::Opaque,
+            blend_mode: transparency::BlendMode::Weighted,
+            sorted,
+            oit,
+            lod,
+            subdivided_mesh: false,
+            lod_ranges: Vec::new(),
+            transparent_ranges: Vec::new(),
             diffuse_bind_group,
             _diffuse_texture: diffuse_texture,
             camera,
             projection,
             camera_controller,
+            orbit_controller,
+            controller_kind: camera::ControllerKind::Fly,
+            #[cfg(feature = "gamepad")]
+            gamepads,
+            camera_path: camera_path::CameraPath::default(),
+            camera_path_file: String::from("camera_path.txt"),
+            bookmarks,
+            bookmark_name: String::new(),
+            modifiers: ModifiersState::empty(),
+            input_map,
+            settings,
+            rebinding: None,
+            screenshot_requested: false,
             camera_uniform,
             camera_buffer,
             camera_bind_group,
             instances,
             instance_buffer,
+            instances_dirty: true,
+            instance_buffer_full: false,
+            transparent_instances: Vec::new(),
+            transparent_buffer,
+            caster_buffer,
+            caster_buffer_current: false,
+            scene_bounds,
+            instance_editor: gui::InstanceEditor::new(NUM_INSTANCES_PER_ROW),
+            highlight,
+            culler,
+            culling_mode: culling::CullingMode::Off,
             depth_texture,
             egui,
             status: Status::default(),
             mouse_pressed: false,
+            cursor_grabbed: false,
+            pointer_over_ui: false,
+            cursor_position: None,
         }
     }
 
     pub fn window(&self) -> &Window {
-        &self.window
+        self.window
+    }
+
+    pub fn instances(&self) -> &[instance::Instance] {
+        &self.instances
+    }
+
+    /// Appends an instance and returns its index. The GPU buffer is updated on
+    /// the next call to `update`.
+    pub fn add_instance(&mut self, instance: instance::Instance) -> usize {
+        self.instances.push(instance);
+        self.instances_dirty = true;
+        self.instances.len() - 1
+    }
+
+    /// Removes the instance at `index`, shifting every following instance down
+    /// by one.
+    pub fn remove_instance(&mut self, index: usize) -> Option<instance::Instance> {
+        if index >= self.instances.len() {
+            return None;
+        }
+        self.instances_dirty = true;
+        Some(self.instances.remove(index))
+    }
+
+    pub fn instance_mut(&mut self, index: usize) -> Option<&mut instance::Instance> {
+        let instance = self.instances.get_mut(index)?;
+        self.instances_dirty = true;
+        Some(instance)
+    }
+
+    pub fn set_instances(&mut self, instances: Vec<instance::Instance>) {
+        self.instances = instances;
+        self.instances_dirty = true;
     }
 
     pub fn resize(&mut self, new_size: egui_winit::winit::dpi::PhysicalSize<u32>) {
@@ -522,48 +656,728 @@ impl<'a> State<'a> {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
-            self.depth_texture =
-                texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
+            self.create_render_targets();
+            self.hdr
+                .resize(&self.device, new_size.width, new_size.height);
+            self.post_process
+                .resize(&self.device, new_size.width, new_size.height, &self.hdr);
             self.projection.resize(new_size.width, new_size.height);
         }
     }
 
+    fn create_render_targets(&mut self) {
+        self.depth_texture = texture::Texture::create_depth_texture(
+            &self.device,
+            &self.config,
+            self.sample_count,
+            "depth_texture",
+        );
+        self.msaa_view = (self.sample_count > 1).then(|| {
+            texture::Texture::create_msaa_texture(
+                &self.device,
+                &self.config,
+                hdr::HDR_FORMAT,
+                self.sample_count,
+                "msaa_texture",
+            )
+        });
+        self.ssao.resize(
+            &self.device,
+            &self.config,
+            &self.depth_texture,
+            self.sample_count,
+        );
+        self.deferred.resize(
+            &self.device,
+            &self.config,
+            &self.depth_texture,
+            self.sample_count,
+        );
+        self.sorted.resize(
+            &self.device,
+            &self.config,
+            &self.render_pipeline_layout,
+            &self.shader,
+            self.sample_count,
+        );
+        self.oit.resize(
+            &self.device,
+            &self.config,
+            &self.render_pipeline_layout,
+            &self.shader,
+            self.sample_count,
+        );
+    }
+
+    /// Switches the MSAA sample count, recreating the render targets and the
+    /// pipelines drawing into them. Counts the adapter can't handle are
+    /// ignored, as is MSAA on the deferred path.
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        if sample_count == self.sample_count
+            || !self.supported_sample_counts.contains(&sample_count)
+            || (self.render_path == deferred::RenderPath::Deferred && sample_count > 1)
+        {
+            return;
+        }
+
+        self.sample_count = sample_count;
+        self.create_render_targets();
+        self.forward_pipelines = ForwardPipelines::new(
+            &self.device,
+            &self.render_pipeline_layout,
+            &self.shader,
+            self.sample_count,
+            self.projection.depth_mode(),
+        );
+        debug!("MSAA set to {}x", sample_count);
+    }
+
+    /// Switches between standard and reverse-Z depth, recreating every
+    /// pipeline testing against the depth buffer.
+    pub fn set_depth_mode(&mut self, depth_mode: camera::DepthMode) {
+        if depth_mode == self.projection.depth_mode() {
+            return;
+        }
+
+        self.projection.set_depth_mode(depth_mode);
+        self.forward_pipelines = ForwardPipelines::new(
+            &self.device,
+            &self.render_pipeline_layout,
+            &self.shader,
+            self.sample_count,
+            depth_mode,
+        );
+        self.deferred.set_depth_mode(
+            &self.device,
+            &self.render_pipeline_layout,
+            &self.shader,
+            depth_mode,
+        );
+        self.sorted.set_depth_mode(
+            &self.device,
+            &self.render_pipeline_layout,
+            &self.shader,
+            depth_mode,
+        );
+        self.oit.set_depth_mode(
+            &self.device,
+            &self.render_pipeline_layout,
+            &self.shader,
+            depth_mode,
+        );
+        debug!("Depth mode set to {:?}", depth_mode);
+        self.settings.depth_mode = depth_mode;
+        self.save_settings();
+    }
+
+    /// Switches the instance mesh, keeping the level of detail settings.
+    /// Thresholds are reset, they belong to the levels of the old mesh.
+    pub fn set_subdivided_mesh(&mut self, subdivided: bool) {
+        if subdivided == self.subdivided_mesh {
+            return;
+        }
+        let mut lod = create_lod_mesh(&self.device, subdivided);
+        lod.enabled = self.lod.enabled;
+        lod.selection = self.lod.selection;
+        lod.fade_band = self.lod.fade_band;
+        self.lod = lod;
+        self.subdivided_mesh = subdivided;
+        debug!("Mesh set to {} levels of detail", self.lod.levels.len());
+    }
+
+    /// Switches between forward and deferred shading. The deferred path reads
+    /// the depth buffer directly, so MSAA gets turned off for it.
+    pub fn set_render_path(&mut self, render_path: deferred::RenderPath) {
+        if render_path == self.render_path {
+            return;
+        }
+        if render_path == deferred::RenderPath::Deferred {
+            self.set_sample_count(1);
+        }
+        self.render_path = render_path;
+        debug!("Render path set to {:?}", render_path);
+    }
+
     pub fn input(&mut self, event: &WindowEvent) -> bool {
+        if let (
+            Some((action, slot)),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            },
+        ) = (self.rebinding, event)
+        {
+            if input::is_bindable(*key) {
+                self.rebinding = None;
+                self.input_map.bind(action, slot, *key);
+                self.save_input_map();
+            }
+            return true;
+        }
+        if self.egui.wants_keyboard_input() {
+            // Releases still reach the controller, so that keys held while
+            // a text field took focus don't stay down
+            if let WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state: ElementState::Released,
+                        ..
+                    },
+                ..
+            } = event
+            {
+                if let Some(action) = self.input_map.action(*key) {
+                    self.process_action(action, ElementState::Released, false);
+                }
+            }
+            if matches!(event, WindowEvent::KeyboardInput { .. }) {
+                return false;
+            }
+        }
         match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Some(*position);
+                false
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_position = None;
+                false
+            }
+            WindowEvent::MouseInput {
+                button: MouseButton::Right,
+                state: ElementState::Pressed,
+                ..
+            } if !self.egui.wants_pointer_input() => {
+                self.select_under_cursor();
+                true
+            }
+            WindowEvent::Focused(false) => {
+                self.set_cursor_grab(false);
+                false
+            }
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
                         physical_key: PhysicalKey::Code(key),
                         state,
+                        repeat,
                         ..
                     },
                 ..
-            } => self.camera_controller.process_keyboard(*key, *state),
+            } => match self.input_map.action(*key) {
+                Some(action) => self.process_action(action, *state, *repeat),
+                None => match bookmark::slot(*key) {
+                    Some(slot) if *state == ElementState::Pressed && !*repeat => {
+                        if self.modifiers.control_key() {
+                            self.add_bookmark(format!("Bookmark {}", slot + 1), Some(slot));
+                        } else if let Some(index) = self.bookmarks.find_slot(slot) {
+                            self.go_to_bookmark(index);
+                        }
+                        true
+                    }
+                    _ => false,
+                },
+            },
+            // Scrolling over the UI is for egui
+            WindowEvent::MouseWheel { .. } if self.egui.wants_pointer_input() => false,
             WindowEvent::MouseWheel { delta, .. } => {
-                self.camera_controller.process_scroll(delta);
+                match (self.projection.kind, self.controller_kind) {
+                    (camera::ProjectionKind::Orthographic, _) => {
+                        self.projection.orthographic.process_scroll(delta)
+                    }
+                    (_, camera::ControllerKind::Fly) => {
+                        self.camera_controller.process_scroll(delta)
+                    }
+                    (_, camera::ControllerKind::Orbit) => {
+                        self.orbit_controller.process_scroll(delta)
+                    }
+                }
                 true
             }
-            WindowEvent::MouseInput {
-                button: MouseButton::Left,
-                state,
-                ..
-            } => {
-                self.mouse_pressed = *state == ElementState::Pressed;
-                true
+            WindowEvent::MouseInput { button, state, .. } => {
+                // Presses over the UI are left to egui. Releases always end a
+                // drag and are passed on too, egui may have seen the press.
+                let pressed = *state == ElementState::Pressed;
+                if pressed && self.egui.wants_pointer_input() {
+                    return false;
+                }
+                let used = match self.controller_kind {
+                    camera::ControllerKind::Fly if *button == MouseButton::Left => {
+                        self.mouse_pressed = pressed;
+                        true
+                    }
+                    camera::ControllerKind::Fly => false,
+                    camera::ControllerKind::Orbit => {
+                        self.orbit_controller.process_button(*button, *state)
+                    }
+                };
+                used && pressed
             }
             _ => false,
         }
     }
 
+    /// The action bound to `key`, if any.
+    pub fn action(&self, key: KeyCode) -> Option<input::Action> {
+        self.input_map.action(key)
+    }
+
+    /// Handles a bound key, returning whether it was used. `Action::Exit` is
+    /// left to the event loop.
+    fn process_action(&mut self, action: input::Action, state: ElementState, repeat: bool) -> bool {
+        let pressed = state == ElementState::Pressed && !repeat;
+        match action {
+            // A grabbed cursor is let go of first, and holding the key
+            // doesn't then go on to exit
+            input::Action::Exit if self.cursor_grabbed || repeat => {
+                if pressed {
+                    self.set_cursor_grab(false);
+                }
+                true
+            }
+            input::Action::Exit => false,
+            input::Action::GrabCursor => {
+                if pressed {
+                    self.set_cursor_grab(!self.cursor_grabbed);
+                }
+                true
+            }
+            input::Action::ToggleProjection => {
+                if pressed {
+                    self.projection.kind = match self.projection.kind {
+                        camera::ProjectionKind::Perspective => camera::ProjectionKind::Orthographic,
+                        camera::ProjectionKind::Orthographic => camera::ProjectionKind::Perspective,
+                    };
+                    debug!("Projection set to {:?}", self.projection.kind);
+                }
+                true
+            }
+            input::Action::Screenshot => {
+                if pressed {
+                    self.screenshot_requested = true;
+                }
+                true
+            }
+            input::Action::FrontView | input::Action::RightView | input::Action::TopView => {
+                if let Some(view) =
+                    camera::AxisView::from_action(action, self.modifiers.control_key())
+                {
+                    if pressed {
+                        self.set_axis_view(view);
+                    }
+                }
+                true
+            }
+            _ => match self.controller_kind {
+                camera::ControllerKind::Fly => self.camera_controller.process_action(action, state),
+                camera::ControllerKind::Orbit => false,
+            },
+        }
+    }
+
+    fn save_input_map(&self) {
+        match self.input_map.save() {
+            Ok(()) => debug!("Key bindings saved to {}", self.input_map.path().display()),
+            Err(e) => error!("{:#}", e),
+        }
+    }
+
+    fn save_settings(&self) {
+        match self.settings.save() {
+            Ok(()) => debug!("Settings saved to {}", self.settings.path().display()),
+            Err(e) => error!("{:#}", e),
+        }
+    }
+
+    /// Raw mouse movement, passed on to the active camera controller.
+    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        match self.controller_kind {
+            camera::ControllerKind::Fly if self.mouse_pressed || self.cursor_grabbed => {
+                self.camera_controller.process_mouse(mouse_dx, mouse_dy)
+            }
+            camera::ControllerKind::Fly => {}
+            camera::ControllerKind::Orbit => {
+                self.orbit_controller.process_mouse(mouse_dx, mouse_dy)
+            }
+        }
+    }
+
+    /// Turns the camera to one of the axis aligned views. The orbit controller
+    /// keeps looking at its target, the fly camera turns in place.
+    fn set_axis_view(&mut self, view: camera::AxisView) {
+        let (yaw, pitch) = view.yaw_pitch();
+        match self.controller_kind {
+            camera::ControllerKind::Fly => {
+                self.camera.yaw = yaw;
+                self.camera.pitch = pitch;
+            }
+            camera::ControllerKind::Orbit => self.orbit_controller.set_angles(yaw, pitch),
+        }
+        debug!("Camera set to the {:?} view", view);
+    }
+
+    /// Bookmarks the current view, see `bookmark::Bookmarks::add`.
+    fn add_bookmark(&mut self, name: String, slot: Option<usize>) {
+        match self
+            .bookmarks
+            .add(name, slot, &self.camera, &self.projection.perspective)
+        {
+            Ok(()) => debug!("Bookmark saved to {}", self.bookmarks.path().display()),
+            Err(e) => error!("{:#}", e),
+        }
+    }
+
+    /// Starts moving to a bookmark, stopping any camera path playing.
+    fn go_to_bookmark(&mut self, index: usize) {
+        if self
+            .bookmarks
+            .go_to(index, &self.camera, &self.projection.perspective)
+        {
+            self.camera_path.stop();
+            debug!("Moving to bookmark {}", index + 1);
+        }
+    }
+
+    /// Switches the camera controller, with the new one taking over from the
+    /// current view.
+    pub fn set_controller_kind(&mut self, kind: camera::ControllerKind) {
+        if kind == self.controller_kind {
+            return;
+        }
+        if kind == camera::ControllerKind::Orbit {
+            self.orbit_controller.look_from(&self.camera);
+        }
+        self.mouse_pressed = false;
+        self.set_cursor_grab(false);
+        self.controller_kind = kind;
+        debug!("Camera controller set to {:?}", kind);
+    }
+
+    /// Selects the instance under the cursor, or the one in the middle of the
+    /// view while the cursor is grabbed. Selects nothing if there is none.
+    fn select_under_cursor(&mut self) {
+        let cursor = if self.cursor_grabbed {
+            (self.size.width as f32 * 0.5, self.size.height as f32 * 0.5)
+        } else if let Some(position) = self.cursor_position {
+            (position.x as f32, position.y as f32)
+        } else {
+            return;
+        };
+        let ray = camera::cursor_ray(
+            &self.camera,
+            self.projection.active(),
+            cursor,
+            self.size.width,
+            self.size.height,
+        );
+        let picked = self.pick(&ray);
+        debug!("Picked instance {:?}", picked);
+        self.instance_editor.select(picked);
+    }
+
+    /// The closest instance hit by `ray`. Tested against the bounding sphere
+    /// first, then against the box of the instance as placed and rotated.
+    pub fn pick(&self, ray: &bounds::Ray) -> Option<usize> {
+        let base = self.lod.base();
+        self.instances
+            .iter()
+            .enumerate()
+            .filter_map(|(index, instance)| {
+                let model = instance.model_matrix();
+                ray.intersect_sphere(&base.sphere.transform(&model))?;
+                // Instances aren't scaled, so distances are the same in their
+                // own space
+                let distance = ray.transform(&model.invert()?).intersect_aabb(&base.aabb)?;
+                Some((index, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// Hides the cursor and keeps it in the window for mouse-look, which the
+    /// fly camera then does without a button held. Locking the cursor in
+    /// place isn't supported everywhere, so it is only confined there.
+    pub fn set_cursor_grab(&mut self, grab: bool) {
+        if grab == self.cursor_grabbed {
+            return;
+        }
+        if grab {
+            if self.controller_kind != camera::ControllerKind::Fly {
+                return;
+            }
+            let result = self
+                .window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined));
+            if let Err(e) = result {
+                error!("Failed to grab the cursor: {}", e);
+                return;
+            }
+            // Out of the way of the UI, where possible
+            let _ = self
+                .window
+                .set_cursor_position(egui_winit::winit::dpi::PhysicalPosition::new(
+                    self.size.width / 2,
+                    self.size.height / 2,
+                ));
+        } else if let Err(e) = self.window.set_cursor_grab(CursorGrabMode::None) {
+            error!("Failed to release the cursor: {}", e);
+        }
+        self.window.set_cursor_visible(!grab);
+        self.cursor_grabbed = grab;
+        debug!("Cursor {}", if grab { "grabbed" } else { "released" });
+    }
+
+    /// Feeds the gamepad into the active controller. The left stick moves,
+    /// the right one looks and the triggers go down and up, or zoom in and
+    /// out when orbiting.
+    #[cfg(feature = "gamepad")]
+    fn poll_gamepads(&mut self) {
+        let Some(gamepads) = &mut self.gamepads else {
+            return;
+        };
+        let pad = gamepads.poll();
+        let rise = pad.right_trigger - pad.left_trigger;
+        match self.controller_kind {
+            camera::ControllerKind::Fly => self.camera_controller.process_stick(
+                cgmath::Vector3::new(pad.left_stick.x, rise, pad.left_stick.y),
+                pad.right_stick,
+                pad.sprinting,
+            ),
+            camera::ControllerKind::Orbit => {
+                self.orbit_controller
+                    .process_stick(pad.right_stick, pad.left_stick, -rise)
+            }
+        }
+    }
+
     pub fn update(&mut self, dt: std::time::Duration) {
-        self.camera_controller.update_camera(&mut self.camera, dt);
+        let pointer_over_ui = self.egui.wants_pointer_input();
+        if pointer_over_ui && !self.pointer_over_ui {
+            self.set_cursor_grab(false);
+        }
+        self.pointer_over_ui = pointer_over_ui;
+
+        if self.instances_dirty {
+            self.scene_bounds = instance_bounds(self.lod.base(), &self.instances);
+            self.transparent_instances = (0..self.instances.len())
+                .filter(|i| self.instances[*i].is_transparent())
+                .collect();
+            self.instance_buffer_full = false;
+            self.caster_buffer_current = false;
+            self.instances_dirty = false;
+        }
+
+        if self.camera_path.is_playing() || self.bookmarks.is_moving() {
+            let perspective = &mut self.projection.perspective;
+            // Not short-circuiting, so that whichever is active advances
+            let moving = self.camera_path.update(dt, &mut self.camera, perspective)
+                | self.bookmarks.update(dt, &mut self.camera, perspective);
+            if !moving {
+                self.orbit_controller.look_from(&self.camera);
+            }
+        } else {
+            #[cfg(feature = "gamepad")]
+            self.poll_gamepads();
+            match self.controller_kind {
+                camera::ControllerKind::Fly => {
+                    self.camera_controller.update_camera(&mut self.camera, dt)
+                }
+                camera::ControllerKind::Orbit => {
+                    self.orbit_controller.update_camera(&mut self.camera, dt)
+                }
+            }
+        }
         self.camera_uniform
-            .update_view_proj(&self.camera, &self.projection);
+            .update_view_proj(&self.camera, self.projection.active());
         self.queue.write_buffer(
             &self.camera_buffer,
             0,
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
+        self.lights.update(&self.device, &self.queue);
+        self.lights
+            .shadow
+            .update(&self.queue, self.lights.sun_direction, &self.scene_bounds);
+
+        let frustum = self.camera_uniform.frustum();
+        let distances = self.lod.switch_distances(self.projection.active());
+        let transparent = self
+            .transparent_instances
+            .iter()
+            .map(|i| &self.instances[*i]);
+        let cull = self.culling_mode != culling::CullingMode::Off;
+        let (mut levels, mut culled) = self.select_levels(transparent, &frustum, &distances, cull);
+        if self.blend_mode == transparency::BlendMode::Sorted {
+            for level in &mut levels {
+                level.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+            }
+        }
+        let (ranges, raw) = flatten_levels(levels);
+        self.transparent_ranges = ranges;
+        self.transparent_buffer
+            .update_raw(&self.device, &self.queue, &raw);
+
+        let static_instances = match self.culling_mode {
+            culling::CullingMode::Off => self.lod.active_levels() == 1,
+            culling::CullingMode::Cpu => false,
+            culling::CullingMode::Gpu => true,
+        };
+        if static_instances {
+            if !self.instance_buffer_full {
+                self.instance_buffer
+                    .update_raw(&self.device, &self.queue, &self.opaque_raw());
+                self.instance_buffer_full = true;
+            }
+            self.lod_ranges = std::iter::once(0..self.instance_buffer.len() as u32).collect();
+            self.status.culled = culled;
+            return;
+        }
+
+        let opaque = self
+            .instances
+            .iter()
+            .filter(|instance| !instance.is_transparent());
+        let cull = self.culling_mode == culling::CullingMode::Cpu;
+        let (levels, opaque_culled) = self.select_levels(opaque, &frustum, &distances, cull);
+        culled += opaque_culled;
+        let (ranges, raw) = flatten_levels(levels);
+        self.lod_ranges = ranges;
+        self.status.culled = culled;
+        self.instance_buffer
+            .update_raw(&self.device, &self.queue, &raw);
+        self.instance_buffer_full = false;
+        if !self.caster_buffer_current {
+            self.caster_buffer
+                .update_raw(&self.device, &self.queue, &self.opaque_raw());
+            self.caster_buffer_current = true;
+        }
+    }
+
+    /// Every opaque instance, for drawing without culling.
+    fn opaque_raw(&self) -> Vec<instance::InstanceRaw> {
+        self.instances
+            .iter()
+            .filter(|instance| !instance.is_transparent())
+            .map(instance::Instance::to_raw)
+            .collect()
+    }
+
+    /// Sorts `instances` into the level of detail they are drawn with, along
+    /// with their distance to the camera. Instances cross-fading between two
+    /// levels are in both. Returns how many were left out by culling against
+    /// `frustum`, when `cull` is set.
+    fn select_levels<'i>(
+        &self,
+        instances: impl Iterator<Item = &'i instance::Instance>,
+        frustum: &camera::Frustum,
+        distances: &[f32; lod::MAX_LOD_LEVELS],
+        cull: bool,
+    ) -> (Vec<Vec<(f32, instance::InstanceRaw)>>, usize) {
+        let base = self.lod.base();
+        let mut levels = vec![Vec::new(); self.lod.active_levels()];
+        let mut culled = 0;
+        for instance in instances {
+            let model = instance.model_matrix();
+            if cull && !base.is_visible(frustum, &model) {
+                culled += 1;
+                continue;
+            }
+
+            let center = model.transform_point(base.sphere.center);
+            let distance = center.distance(self.camera.position);
+            let (level, fade) = self.lod.select(distances, distance);
+            let raw = instance.to_raw();
+            levels[level].push((distance, raw.with_lod_fade(fade)));
+            if fade > 0.0 {
+                levels[level + 1].push((distance, raw.with_lod_fade(-fade)));
+            }
+        }
+        (levels, culled)
+    }
+
+    /// The alpha mode in effect, as the deferred path only draws opaque.
+    fn alpha_mode(&self) -> transparency::AlphaMode {
+        match self.render_path {
+            deferred::RenderPath::Forward => self.alpha_mode,
+            deferred::RenderPath::Deferred => transparency::AlphaMode::Opaque,
+        }
+    }
+
+    /// Sets the bind groups of the scene, for drawing it with a pipeline that
+    /// has to be set already.
+    fn set_scene_bind_groups<'p>(&'p self, render_pass: &mut egui_wgpu::wgpu::RenderPass<'p>) {
+        render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.lights.bind_group, &[]);
+        render_pass.set_bind_group(3, &self.light_clusters.bind_group, &[]);
+    }
+
+    /// Draws every active level of detail of the opaque instances with the
+    /// bind groups of the scene.
+    fn draw_scene<'p>(
+        &'p self,
+        render_pass: &mut egui_wgpu::wgpu::RenderPass<'p>,
+        gpu_culling: bool,
+    ) {
+        self.set_scene_bind_groups(render_pass);
+        self.draw_instances(render_pass, gpu_culling);
+    }
+
+    /// Draws the transparent instances with the bind groups of the scene,
+    /// farther levels of detail first to keep sorted ones back to front.
+    fn draw_transparent_scene<'p>(&'p self, render_pass: &mut egui_wgpu::wgpu::RenderPass<'p>) {
+        self.set_scene_bind_groups(render_pass);
+        render_pass.set_vertex_buffer(1, self.transparent_buffer.buffer.slice(..));
+        for (level, range) in self.lod.levels.iter().zip(&self.transparent_ranges).rev() {
+            render_pass.set_vertex_buffer(0, level.mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(
+                level.mesh.index_buffer.slice(..),
+                egui_wgpu::wgpu::IndexFormat::Uint16,
+            );
+            render_pass.draw_indexed(0..level.mesh.num_indices, 0, range.clone());
+        }
+    }
+
+    /// Draws every active level of detail with whatever bind groups are set.
+    fn draw_instances<'p>(
+        &'p self,
+        render_pass: &mut egui_wgpu::wgpu::RenderPass<'p>,
+        gpu_culling: bool,
+    ) {
+        for (i, level) in self
+            .lod
+            .levels
+            .iter()
+            .enumerate()
+            .take(self.lod.active_levels())
+        {
+            render_pass.set_vertex_buffer(0, level.mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(
+                level.mesh.index_buffer.slice(..),
+                egui_wgpu::wgpu::IndexFormat::Uint16,
+            );
+            if gpu_culling {
+                render_pass.set_vertex_buffer(1, self.culler.visible_instances(i));
+                render_pass.draw_indexed_indirect(
+                    &self.culler.indirect_buffer,
+                    culling::GpuCuller::indirect_offset(i),
+                );
+            } else if let Some(range) = self.lod_ranges.get(i) {
+                render_pass.set_vertex_buffer(1, self.instance_buffer.buffer.slice(..));
+                render_pass.draw_indexed(0..level.mesh.num_indices, 0, range.clone());
+            }
+        }
     }
 
     pub fn render(&mut self) -> Result<(), egui_wgpu::wgpu::SurfaceError> {
@@ -579,44 +1393,183 @@ impl<'a> State<'a> {
                     label: Some("Render Encoder"),
                 });
 
-        {
-            let mut render_pass =
-                encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
-                    label: Some("Render Pass"),
-                    color_attachments: &[Some(egui_wgpu::wgpu::RenderPassColorAttachment {
-                        view: &view,
-                        resolve_target: None,
-                        ops: egui_wgpu::wgpu::Operations {
-                            load: egui_wgpu::wgpu::LoadOp::Clear(self.clear_color),
-                            store: egui_wgpu::wgpu::StoreOp::Store,
-                        },
-                    })],
-                    depth_stencil_attachment: Some(
-                        egui_wgpu::wgpu::RenderPassDepthStencilAttachment {
-                            view: &self.depth_texture.view,
-                            depth_ops: Some(egui_wgpu::wgpu::Operations {
-                                load: egui_wgpu::wgpu::LoadOp::Clear(1.0),
-                                store: egui_wgpu::wgpu::StoreOp::Store,
-                            }),
-                            stencil_ops: None,
-                        },
-                    ),
-                    occlusion_query_set: None,
-                    timestamp_writes: None,
-                });
+        let gpu_culling = self.culling_mode == culling::CullingMode::Gpu;
+        if gpu_culling {
+            self.culler.cull(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                &self.instance_buffer,
+                &self.camera_uniform.frustum(),
+                self.camera.position,
+                &self.lod,
+                &self.lod.switch_distances(self.projection.active()),
+            );
+        }
 
-            render_pass.set_pipeline(&self.render_pipeline);
+        // Every opaque instance casts a shadow, also the ones out of view, so
+        // casters come from an unculled buffer and are drawn at full detail
+        if self.lights.shadow.enabled {
+            let cutout = self.alpha_mode() == transparency::AlphaMode::Cutout;
+            let casters = if self.instance_buffer_full {
+                &self.instance_buffer
+            } else {
+                &self.caster_buffer
+            };
+            let base = self.lod.base();
+            let mut render_pass = self.lights.shadow.begin_pass(&mut encoder, cutout);
             render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+            render_pass.set_vertex_buffer(0, base.vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, casters.buffer.slice(..));
             render_pass.set_index_buffer(
-                self.index_buffer.slice(..),
+                base.index_buffer.slice(..),
                 egui_wgpu::wgpu::IndexFormat::Uint16,
             );
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..self.instances.len() as _);
+            render_pass.draw_indexed(0..base.num_indices, 0, 0..casters.len() as u32);
         }
 
+        match self.render_path {
+            deferred::RenderPath::Forward => {
+                self.light_clusters.cull(
+                    &self.queue,
+                    &mut encoder,
+                    &self.camera,
+                    self.projection.active(),
+                    self.size.width,
+                    self.size.height,
+                    &self.lights.bind_group,
+                );
+                {
+                    let mut render_pass =
+                        encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
+                            label: Some("Render Pass"),
+                            color_attachments: &[Some(
+                                egui_wgpu::wgpu::RenderPassColorAttachment {
+                                    view: self.msaa_view.as_ref().unwrap_or(self.hdr.view()),
+                                    resolve_target: self
+                                        .msaa_view
+                                        .as_ref()
+                                        .map(|_| self.hdr.view()),
+                                    ops: egui_wgpu::wgpu::Operations {
+                                        load: egui_wgpu::wgpu::LoadOp::Clear(self.clear_color),
+                                        store: egui_wgpu::wgpu::StoreOp::Store,
+                                    },
+                                },
+                            )],
+                            depth_stencil_attachment: Some(
+                                egui_wgpu::wgpu::RenderPassDepthStencilAttachment {
+                                    view: &self.depth_texture.view,
+                                    depth_ops: Some(egui_wgpu::wgpu::Operations {
+                                        load: egui_wgpu::wgpu::LoadOp::Clear(
+                                            self.projection.depth_mode().far_depth(),
+                                        ),
+                                        store: egui_wgpu::wgpu::StoreOp::Store,
+                                    }),
+                                    stencil_ops: None,
+                                },
+                            ),
+                            occlusion_query_set: None,
+                            timestamp_writes: None,
+                        });
+                    render_pass.set_pipeline(self.forward_pipelines.get(self.alpha_mode));
+                    self.draw_scene(&mut render_pass, gpu_culling);
+                }
+            }
+            deferred::RenderPath::Deferred => {
+                // Transparent instances are lit in a forward pass on top
+                if !self.transparent_buffer.is_empty() {
+                    self.light_clusters.cull(
+                        &self.queue,
+                        &mut encoder,
+                        &self.camera,
+                        self.projection.active(),
+                        self.size.width,
+                        self.size.height,
+                        &self.lights.bind_group,
+                    );
+                }
+                {
+                    let mut render_pass = self
+                        .deferred
+                        .begin_gbuffer_pass(&mut encoder, &self.depth_texture.view);
+                    self.draw_scene(&mut render_pass, gpu_culling);
+                }
+                self.deferred.light(
+                    &mut encoder,
+                    self.hdr.view(),
+                    &self.camera_bind_group,
+                    &self.lights.bind_group,
+                    self.clear_color,
+                );
+            }
+        }
+
+        // Occlusion only darkens the opaque surfaces, as only they wrote depth
+        self.ssao.render(
+            &self.queue,
+            &mut encoder,
+            self.projection.active(),
+            self.hdr.view(),
+        );
+
+        if !self.transparent_buffer.is_empty() {
+            match self.blend_mode {
+                transparency::BlendMode::Sorted => {
+                    {
+                        let mut render_pass = self
+                            .sorted
+                            .begin_pass(&mut encoder, &self.depth_texture.view);
+                        self.draw_transparent_scene(&mut render_pass);
+                    }
+                    self.sorted.composite(&mut encoder, self.hdr.view());
+                }
+                transparency::BlendMode::Weighted => {
+                    {
+                        let mut render_pass = self
+                            .oit
+                            .begin_accumulate_pass(&mut encoder, &self.depth_texture.view);
+                        self.draw_transparent_scene(&mut render_pass);
+                    }
+                    self.oit.composite(&mut encoder, self.hdr.view());
+                }
+            }
+        }
+
+        // Tonemap before egui draws on top so the overlay ignores exposure
+        // and post processing
+        self.post_process
+            .render(&self.queue, &mut encoder, &self.hdr, &view);
+        let screenshot = if std::mem::take(&mut self.screenshot_requested) {
+            screenshot::Screenshot::capture(&self.device, &mut encoder, &output.texture)
+                .map_err(|e| error!("{:#}", e))
+                .ok()
+        } else {
+            None
+        };
+        if let Some(instance) = self
+            .instance_editor
+            .selected
+            .and_then(|index| self.instances.get(index))
+        {
+            self.highlight.render(
+                &self.queue,
+                &mut encoder,
+                &view,
+                self.projection.active().calc_matrix() * self.camera.calc_matrix(),
+                instance.model_matrix(),
+                &self.lod.base().aabb,
+            );
+        }
+
+        let mut sample_count = self.sample_count;
+        let mut render_path = self.render_path;
+        let mut controller_kind = self.controller_kind;
+        let mut cursor_grabbed = self.cursor_grabbed;
+        let mut depth_mode = self.projection.depth_mode();
+        let mut subdivided_mesh = self.subdivided_mesh;
+        let mut add_bookmark = None;
+        let mut go_to_bookmark = None;
+        let mut remove_bookmark = None;
         let screen_descriptor = egui_wgpu::ScreenDescriptor {
             size_in_pixels: [self.size.width, self.size.height],
             pixels_per_point: 1.0,
@@ -630,7 +1583,7 @@ impl<'a> State<'a> {
             &view,
             &screen_descriptor,
             |ui| {
-                egui::Window::new("Debug").show(&ui, |ui| {
+                egui::Window::new("Debug").show(ui, |ui| {
                     ui.label(format!("FPS: {:.2}", self.status.fps));
                     ui.label(format!("Avg FPS: {:.2}", self.status.fps_avg));
                     ui.label(format!(
@@ -643,28 +1596,695 @@ impl<'a> State<'a> {
                     ui.label(format!("Width: {}", self.size.width));
                     ui.label(format!("Height: {}", self.size.height));
                     ui.separator();
+                    ui.label("Render Path");
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut render_path, deferred::RenderPath::Forward, "Forward");
+                        ui.radio_value(
+                            &mut render_path,
+                            deferred::RenderPath::Deferred,
+                            "Deferred",
+                        );
+                    });
+                    ui.separator();
+                    ui.label("Anti-aliasing");
+                    ui.add_enabled_ui(self.render_path == deferred::RenderPath::Forward, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("MSAA");
+                            for count in &self.supported_sample_counts {
+                                ui.radio_value(&mut sample_count, *count, format!("{}x", count));
+                            }
+                        });
+                    });
+                    // Also listed under post processing, repeated here to
+                    // compare it against MSAA
+                    if let Some(fxaa) = self.post_process.effects_mut().iter_mut().find(|effect| {
+                        matches!(effect.settings, postprocess::EffectSettings::Fxaa { .. })
+                    }) {
+                        ui.checkbox(&mut fxaa.enabled, "FXAA");
+                    }
+                    ui.separator();
+                    ui.label("Transparency");
+                    ui.add_enabled_ui(self.render_path == deferred::RenderPath::Forward, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Alpha");
+                            for mode in transparency::AlphaMode::ALL {
+                                ui.radio_value(&mut self.alpha_mode, mode, mode.name());
+                            }
+                        });
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Blending");
+                        for mode in transparency::BlendMode::ALL {
+                            ui.radio_value(&mut self.blend_mode, mode, mode.name());
+                        }
+                    });
+                    ui.label(format!(
+                        "{} transparent instances, see Opacity of the selected instance",
+                        self.transparent_instances.len()
+                    ));
+                    ui.separator();
+                    ui.label("Tonemapping");
+                    ui.horizontal(|ui| {
+                        for tonemapper in hdr::Tonemapper::ALL {
+                            ui.radio_value(&mut self.hdr.tonemapper, tonemapper, tonemapper.name());
+                        }
+                    });
+                    ui.add(
+                        egui::Slider::new(&mut self.hdr.exposure, 0.0..=8.0)
+                            .logarithmic(true)
+                            .text("Exposure"),
+                    );
+                    ui.separator();
+                    ui.label("Ambient Occlusion");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.ssao.enabled, "SSAO");
+                        ui.checkbox(&mut self.ssao.debug_view, "Show raw AO");
+                        ui.checkbox(&mut self.ssao.depth_view, "Show depth");
+                    });
+                    ui.add(egui::Slider::new(&mut self.ssao.radius, 0.05..=2.0).text("Radius"));
+                    ui.add(
+                        egui::Slider::new(&mut self.ssao.intensity, 0.1..=4.0).text("Intensity"),
+                    );
+                    ui.add(egui::Slider::new(&mut self.ssao.bias, 0.0..=0.1).text("Bias"));
+                    ui.add(
+                        egui::Slider::new(
+                            &mut self.ssao.kernel_size,
+                            1..=ssao::MAX_KERNEL_SIZE as u32,
+                        )
+                        .text("Samples"),
+                    );
+                    ui.separator();
+                    ui.label("Lights");
+                    let mut light_count = self.lights.point_lights.len();
+                    ui.add(egui::Slider::new(&mut light_count, 0..=1024).text("Point lights"));
+                    if light_count != self.lights.point_lights.len() {
+                        self.lights.point_lights =
+                            light::scatter(light_count, NUM_INSTANCES_PER_ROW as f32);
+                    }
+                    if self.render_path == deferred::RenderPath::Forward {
+                        let [x, y, z] = cluster::GRID_SIZE;
+                        ui.label(format!(
+                            "Clusters: {}x{}x{}, up to {} lights each",
+                            x,
+                            y,
+                            z,
+                            cluster::MAX_LIGHTS_PER_CLUSTER
+                        ));
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Sun");
+                        ui.color_edit_button_rgb(&mut self.lights.sun_color);
+                        ui.label("Ambient");
+                        ui.color_edit_button_rgb(&mut self.lights.ambient);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.lights.shadow.enabled, "Sun shadows");
+                        ui.add(
+                            egui::Slider::new(&mut self.lights.shadow.bias, 0.0..=0.01)
+                                .text("Bias"),
+                        );
+                    });
+                    ui.separator();
+                    ui.label("Post Processing");
+                    gui::effect_stack(ui, self.post_process.effects_mut());
+                    ui.separator();
                     ui.label("Camera");
+                    ui.horizontal(|ui| {
+                        ui.label("Controller");
+                        ui.radio_value(&mut controller_kind, camera::ControllerKind::Fly, "Fly");
+                        ui.radio_value(
+                            &mut controller_kind,
+                            camera::ControllerKind::Orbit,
+                            "Orbit",
+                        );
+                    });
+                    if self.controller_kind == camera::ControllerKind::Fly {
+                        let controller = &mut self.camera_controller;
+                        ui.add(
+                            egui::Slider::new(&mut controller.speed, 0.1..=1000.0)
+                                .logarithmic(true)
+                                .text("Speed (Alt + Scroll)"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut controller.sprint_multiplier, 1.0..=10.0)
+                                .text("Sprint Multiplier (Ctrl)"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut controller.sensitivity, 0.1..=5.0)
+                                .text("Sensitivity"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut controller.smoothing, 0.0..=0.5)
+                                .text("Rotation Smoothing (s)"),
+                        );
+                        ui.checkbox(&mut cursor_grabbed, "Grab Cursor (G, Escape releases)");
+                        ui.checkbox(&mut controller.inertia, "Inertia");
+                        ui.add_enabled(
+                            controller.inertia,
+                            egui::Slider::new(&mut controller.acceleration, 1.0..=200.0)
+                                .logarithmic(true)
+                                .text("Acceleration"),
+                        );
+                        ui.add_enabled(
+                            controller.inertia,
+                            egui::Slider::new(&mut controller.damping, 0.1..=50.0)
+                                .logarithmic(true)
+                                .text("Damping"),
+                        );
+                    }
+                    if self.controller_kind == camera::ControllerKind::Orbit {
+                        ui.label(format!("Orbit Target: {:?}", self.orbit_controller.target));
+                        ui.add(
+                            egui::Slider::new(&mut self.orbit_controller.distance, 0.1..=100.0)
+                                .logarithmic(true)
+                                .text("Orbit Distance"),
+                        );
+                    }
+                    #[cfg(feature = "gamepad")]
+                    if let Some(gamepads) = &mut self.gamepads {
+                        ui.label(format!(
+                            "Gamepad: {}",
+                            gamepads.active_name().as_deref().unwrap_or("None")
+                        ));
+                        ui.add(
+                            egui::Slider::new(&mut gamepads.deadzone, 0.0..=0.5)
+                                .text("Gamepad Deadzone"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut gamepads.sensitivity, 0.1..=5.0)
+                                .text("Gamepad Sensitivity"),
+                        );
+                        ui.checkbox(&mut gamepads.invert_y, "Invert Gamepad Y");
+                    }
                     ui.label(format!("Camera Position: {:?}", self.camera.position));
                     ui.label(format!("Camera Yaw: {:?}", self.camera.yaw));
                     ui.label(format!("Camera Pitch: {:?}", self.camera.pitch));
                     ui.separator();
+                    ui.label("Camera Path");
+                    ui.horizontal(|ui| {
+                        if ui.button("Record Keyframe").clicked() {
+                            self.camera_path
+                                .record(&self.camera, &self.projection.perspective);
+                        }
+                        if ui.button("Clear").clicked() {
+                            self.camera_path.clear();
+                        }
+                        ui.label(format!("Keyframes: {}", self.camera_path.keyframes.len()));
+                    });
+                    let mut duration = self.camera_path.duration.as_secs_f32();
+                    if ui
+                        .add(
+                            egui::Slider::new(&mut duration, 1.0..=120.0)
+                                .logarithmic(true)
+                                .text("Duration (s)"),
+                        )
+                        .changed()
+                    {
+                        self.camera_path.duration = std::time::Duration::from_secs_f32(duration);
+                    }
+                    ui.checkbox(&mut self.camera_path.looping, "Loop");
+                    ui.horizontal(|ui| {
+                        if self.camera_path.is_playing() {
+                            if ui.button("Stop").clicked() {
+                                self.camera_path.stop();
+                                self.orbit_controller.look_from(&self.camera);
+                            }
+                        } else if ui
+                            .add_enabled(
+                                self.camera_path.keyframes.len() >= 2,
+                                egui::Button::new("Play"),
+                            )
+                            .clicked()
+                        {
+                            self.camera_path.play();
+                        }
+                        if let Some(progress) = self.camera_path.progress() {
+                            ui.add(egui::ProgressBar::new(progress));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.camera_path_file);
+                        let path = std::path::Path::new(&self.camera_path_file);
+                        if ui.button("Save").clicked() {
+                            match self.camera_path.save(path) {
+                                Ok(()) => debug!("Camera path saved to {}", path.display()),
+                                Err(e) => error!("{:#}", e),
+                            }
+                        }
+                        if ui.button("Load").clicked() {
+                            match self.camera_path.load(path) {
+                                Ok(()) => debug!(
+                                    "Loaded {} keyframes from {}",
+                                    self.camera_path.keyframes.len(),
+                                    path.display()
+                                ),
+                                Err(e) => error!("{:#}", e),
+                            }
+                        }
+                    });
+                    ui.separator();
+                    ui.label("Bookmarks");
+                    for (i, bookmark) in self.bookmarks.bookmarks.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui.button("Go").clicked() {
+                                go_to_bookmark = Some(i);
+                            }
+                            if ui.button("Delete").clicked() {
+                                remove_bookmark = Some(i);
+                            }
+                            match bookmark.slot {
+                                Some(slot) => ui.label(format!("{}: {}", slot + 1, bookmark.name)),
+                                None => ui.label(&bookmark.name),
+                            };
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.bookmark_name);
+                        if ui.button("Add").clicked() {
+                            let name = match self.bookmark_name.trim() {
+                                "" => format!("Bookmark {}", self.bookmarks.bookmarks.len() + 1),
+                                name => name.to_string(),
+                            };
+                            add_bookmark = Some(name);
+                            self.bookmark_name.clear();
+                        }
+                    });
+                    let mut transition = self.bookmarks.transition_duration.as_secs_f32();
+                    if ui
+                        .add(egui::Slider::new(&mut transition, 0.0..=3.0).text("Transition (s)"))
+                        .changed()
+                    {
+                        self.bookmarks.transition_duration =
+                            std::time::Duration::from_secs_f32(transition);
+                    }
+                    ui.label("1-9: go to bookmark, Ctrl + 1-9: store the view");
+                    ui.separator();
+                    ui.label("Key Bindings");
+                    let mut bindings_changed = false;
+                    egui::Grid::new("key_bindings").show(ui, |ui| {
+                        for action in input::Action::ALL {
+                            ui.label(action.name());
+                            ui.horizontal(|ui| {
+                                let keys = self.input_map.keys(action).len();
+                                for slot in 0..=keys {
+                                    let text = if self.rebinding == Some((action, slot)) {
+                                        "Press a key...".to_string()
+                                    } else if slot < keys {
+                                        format!("{:?}", self.input_map.keys(action)[slot])
+                                    } else {
+                                        "+".to_string()
+                                    };
+                                    let response = ui.button(text);
+                                    if response.clicked() {
+                                        self.rebinding = match self.rebinding {
+                                            Some(rebinding) if rebinding == (action, slot) => None,
+                                            _ => Some((action, slot)),
+                                        };
+                                    }
+                                    if response.secondary_clicked() && slot < keys {
+                                        self.input_map.unbind(action, slot);
+                                        self.rebinding = None;
+                                        bindings_changed = true;
+                                    }
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+                    ui.label("Click a key to rebind it, right click to remove it");
+                    if ui.button("Reset to Defaults").clicked() {
+                        self.input_map.reset();
+                        self.rebinding = None;
+                        bindings_changed = true;
+                    }
+                    if bindings_changed {
+                        match self.input_map.save() {
+                            Ok(()) => {
+                                debug!("Key bindings saved to {}", self.input_map.path().display())
+                            }
+                            Err(e) => error!("{:#}", e),
+                        }
+                    }
+                    ui.separator();
                     ui.label("Projection");
-                    ui.label(format!("Aspect: {}", self.projection.aspect));
-                    ui.label(format!("Fovy: {:?}", self.projection.fovy));
-                    ui.label(format!("Znear: {}", self.projection.znear));
-                    ui.label(format!("Zfar: {}", self.projection.zfar));
+                    ui.horizontal(|ui| {
+                        ui.radio_value(
+                            &mut self.projection.kind,
+                            camera::ProjectionKind::Perspective,
+                            "Perspective",
+                        );
+                        ui.radio_value(
+                            &mut self.projection.kind,
+                            camera::ProjectionKind::Orthographic,
+                            "Orthographic",
+                        );
+                    });
+                    match self.projection.kind {
+                        camera::ProjectionKind::Perspective => {
+                            let perspective = &self.projection.perspective;
+                            ui.label(format!("Aspect: {}", perspective.aspect));
+                            ui.label(format!("Fovy: {:?}", perspective.fovy));
+                        }
+                        camera::ProjectionKind::Orthographic => {
+                            let orthographic = &mut self.projection.orthographic;
+                            ui.label(format!("Aspect: {}", orthographic.aspect));
+                            ui.add(
+                                egui::Slider::new(&mut orthographic.height, 0.01..=200.0)
+                                    .logarithmic(true)
+                                    .text("View Height"),
+                            );
+                        }
+                    }
+                    let projection = self.projection.active();
+                    ui.label(format!("Znear: {}", projection.znear()));
+                    ui.label(format!("Zfar: {}", projection.zfar()));
+                    ui.horizontal(|ui| {
+                        ui.label("Depth");
+                        for mode in camera::DepthMode::ALL {
+                            ui.radio_value(&mut depth_mode, mode, mode.name());
+                        }
+                    });
+                    if depth_mode == camera::DepthMode::ReverseZ
+                        && self.projection.kind == camera::ProjectionKind::Perspective
+                    {
+                        ui.label("Far plane at infinity");
+                    }
+                    ui.label("Numpad 1/3/7: front/right/top view, Ctrl for the opposite");
+                    ui.label("Numpad 5: toggle orthographic");
                     ui.separator();
                     ui.label("Instances");
-                    ui.label(format!("Instances per row: {}", NUM_INSTANCES_PER_ROW));
                     ui.label(format!("Amount of Instances: {}", self.instances.len()));
-                    ui.label(format!("Amount triangles: {}", self.instances.len() * 2));
+                    let triangles = self
+                        .lod
+                        .levels
+                        .iter()
+                        .zip(&self.lod_ranges)
+                        .map(|(level, range)| range.len() * level.mesh.num_indices as usize / 3)
+                        .sum::<usize>();
+                    ui.label(format!("Amount triangles: {}", triangles));
+                    ui.horizontal(|ui| {
+                        ui.label("Frustum culling");
+                        ui.radio_value(&mut self.culling_mode, culling::CullingMode::Off, "Off");
+                        ui.radio_value(&mut self.culling_mode, culling::CullingMode::Cpu, "CPU");
+                        ui.radio_value(&mut self.culling_mode, culling::CullingMode::Gpu, "GPU");
+                    });
+                    match self.culling_mode {
+                        culling::CullingMode::Off => {}
+                        culling::CullingMode::Cpu => {
+                            ui.label(format!("Culled Instances: {}", self.status.culled));
+                        }
+                        culling::CullingMode::Gpu => {
+                            ui.label("Culled Instances: counted on the GPU");
+                        }
+                    }
+                    ui.label("Right click an instance to select it");
+                    if self.instance_editor.ui(ui, &mut self.instances) {
+                        self.instances_dirty = true;
+                    }
+                    ui.separator();
+                    ui.label("Level of Detail");
+                    ui.checkbox(
+                        &mut subdivided_mesh,
+                        "Subdivided mesh (8x8 quad with LOD levels)",
+                    );
+                    ui.checkbox(&mut self.lod.enabled, "Enabled");
+                    ui.horizontal(|ui| {
+                        ui.label("Selection");
+                        ui.radio_value(
+                            &mut self.lod.selection,
+                            lod::LodSelection::Distance,
+                            "Distance",
+                        );
+                        ui.radio_value(
+                            &mut self.lod.selection,
+                            lod::LodSelection::ScreenSize,
+                            "Screen size",
+                        );
+                    });
+                    ui.add(
+                        egui::Slider::new(&mut self.lod.fade_band, 0.0..=0.5).text("Cross-fade"),
+                    );
+                    let last = self.lod.levels.len() - 1;
+                    let selection = self.lod.selection;
+                    for (i, level) in self.lod.levels.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "LOD{}: {} triangles",
+                                i,
+                                level.mesh.num_indices / 3
+                            ));
+                            if i == last {
+                                return;
+                            }
+                            match selection {
+                                lod::LodSelection::Distance => ui.add(
+                                    egui::DragValue::new(&mut level.distance)
+                                        .speed(0.1)
+                                        .clamp_range(0.0..=f32::MAX)
+                                        .prefix("until "),
+                                ),
+                                lod::LodSelection::ScreenSize => ui.add(
+                                    egui::DragValue::new(&mut level.screen_size)
+                                        .speed(0.001)
+                                        .clamp_range(0.0..=1.0)
+                                        .prefix("until "),
+                                ),
+                            };
+                        });
+                    }
                 });
             },
         );
 
         self.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(screenshot) = screenshot {
+            let seconds = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs());
+            let path = std::path::PathBuf::from(format!("screenshot-{}.png", seconds));
+            match screenshot.save(&self.device, &path) {
+                Ok(()) => info!("Screenshot saved to {}", path.display()),
+                Err(e) => error!("{:#}", e),
+            }
+        }
         output.present();
 
+        // The frame above still used the old targets, so only switch now
+        self.set_render_path(render_path);
+        self.set_controller_kind(controller_kind);
+        self.set_cursor_grab(cursor_grabbed);
+        self.set_sample_count(sample_count);
+        self.set_depth_mode(depth_mode);
+        self.set_subdivided_mesh(subdivided_mesh);
+        if let Some(name) = add_bookmark {
+            self.add_bookmark(name, None);
+        }
+        if let Some(index) = go_to_bookmark {
+            self.go_to_bookmark(index);
+        }
+        if let Some(index) = remove_bookmark {
+            if let Err(e) = self.bookmarks.remove(index) {
+                error!("{:#}", e);
+            }
+        }
+
         Ok(())
     }
 }
+
+/// Box around every instance of `mesh`, loose as it goes by their bounding
+/// spheres.
+fn instance_bounds(mesh: &mesh::Mesh, instances: &[instance::Instance]) -> bounds::Aabb {
+    bounds::Aabb::from_points(instances.iter().flat_map(|instance| {
+        let sphere = mesh.sphere.transform(&instance.model_matrix());
+        let extent = cgmath::Vector3::from_value(sphere.radius);
+        [sphere.center - extent, sphere.center + extent]
+    }))
+}
+
+/// Lays out the `levels` from `State::select_levels` one after another,
+/// returning the range each one ends up in.
+fn flatten_levels(
+    levels: Vec<Vec<(f32, instance::InstanceRaw)>>,
+) -> (Vec<std::ops::Range<u32>>, Vec<instance::InstanceRaw>) {
+    let mut start = 0;
+    let ranges = levels
+        .iter()
+        .map(|level| {
+            let range = start..start + level.len() as u32;
+            start = range.end;
+            range
+        })
+        .collect();
+    let raw = levels.into_iter().flatten().map(|(_, raw)| raw).collect();
+    (ranges, raw)
+}
+
+/// The quad every instance is drawn with. The plain one is just two
+/// triangles, the subdivided one has enough of them to simplify into levels
+/// of detail.
+fn create_lod_mesh(device: &egui_wgpu::wgpu::Device, subdivided: bool) -> lod::LodMesh {
+    if subdivided {
+        let (vertices, indices) = mesh::quad(8);
+        lod::LodMesh::generate(device, "Subdivided Quad", &vertices, &indices, &[4, 2])
+    } else {
+        let (vertices, indices) = mesh::quad(1);
+        lod::LodMesh::generate(device, "Quad", &vertices, &indices, &[])
+    }
+}
+
+/// Renames a file which failed to load to end in `.bad`, so that starting
+/// over with defaults doesn't save over what was in it.
+fn set_aside(path: &std::path::Path) {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bad");
+    match std::fs::rename(path, &backup) {
+        Ok(()) => warn!(
+            "Moved {} to {}, fix it and move it back to keep using it",
+            path.display(),
+            std::path::Path::new(&backup).display()
+        ),
+        Err(e) => error!("Failed to move {} aside: {}", path.display(), e),
+    }
+}
+
+/// MSAA sample counts usable for rendering into `format` with a depth buffer.
+fn supported_sample_counts(
+    adapter: &egui_wgpu::wgpu::Adapter,
+    device: &egui_wgpu::wgpu::Device,
+    format: egui_wgpu::wgpu::TextureFormat,
+) -> Vec<u32> {
+    if !device
+        .features()
+        .contains(egui_wgpu::wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+    {
+        return vec![1, 4];
+    }
+
+    let color = adapter.get_texture_format_features(format).flags;
+    let depth = adapter
+        .get_texture_format_features(texture::Texture::DEPTH_FORMAT)
+        .flags;
+    [1, 2, 4, 8]
+        .into_iter()
+        .filter(|count| {
+            color.sample_count_supported(*count) && depth.sample_count_supported(*count)
+        })
+        .collect()
+}
+
+/// The forward pass's pipelines drawing straight into the HDR target, one
+/// for each alpha mode of opaque instances.
+struct ForwardPipelines {
+    opaque: egui_wgpu::wgpu::RenderPipeline,
+    cutout: egui_wgpu::wgpu::RenderPipeline,
+}
+
+impl ForwardPipelines {
+    fn new(
+        device: &egui_wgpu::wgpu::Device,
+        layout: &egui_wgpu::wgpu::PipelineLayout,
+        shader: &egui_wgpu::wgpu::ShaderModule,
+        sample_count: u32,
+        depth_mode: camera::DepthMode,
+    ) -> Self {
+        let pipeline = |entry_point, blend, alpha_mode| {
+            create_render_pipeline(
+                device,
+                layout,
+                shader,
+                entry_point,
+                &[Some(egui_wgpu::wgpu::ColorTargetState {
+                    format: hdr::HDR_FORMAT,
+                    blend: Some(blend),
+                    write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
+                })],
+                alpha_mode,
+                sample_count,
+                depth_mode,
+            )
+        };
+        let cutout_entry_point = if sample_count > 1 {
+            "fs_cutout_coverage"
+        } else {
+            "fs_cutout"
+        };
+        Self {
+            opaque: pipeline(
+                "fs_main",
+                egui_wgpu::wgpu::BlendState::REPLACE,
+                Some(transparency::AlphaMode::Opaque),
+            ),
+            cutout: pipeline(
+                cutout_entry_point,
+                egui_wgpu::wgpu::BlendState::REPLACE,
+                Some(transparency::AlphaMode::Cutout),
+            ),
+        }
+    }
+
+    fn get(&self, alpha_mode: transparency::AlphaMode) -> &egui_wgpu::wgpu::RenderPipeline {
+        match alpha_mode {
+            transparency::AlphaMode::Opaque => &self.opaque,
+            transparency::AlphaMode::Cutout => &self.cutout,
+        }
+    }
+}
+
+/// Pipeline drawing the instanced scene with the fragment shader
+/// `entry_point` into `targets`. Transparent surfaces, without an
+/// `alpha_mode`, are depth tested without writing depth, cutouts use alpha
+/// to coverage under MSAA.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn create_render_pipeline(
+    device: &egui_wgpu::wgpu::Device,
+    layout: &egui_wgpu::wgpu::PipelineLayout,
+    shader: &egui_wgpu::wgpu::ShaderModule,
+    entry_point: &str,
+    targets: &[Option<egui_wgpu::wgpu::ColorTargetState>],
+    alpha_mode: Option<transparency::AlphaMode>,
+    sample_count: u32,
+    depth_mode: camera::DepthMode,
+) -> egui_wgpu::wgpu::RenderPipeline {
+    device.create_render_pipeline(&egui_wgpu::wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(layout),
+        vertex: egui_wgpu::wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[mesh::Vertex::desc(), instance::InstanceRaw::desc()],
+            // compilation_options: egui_wgpu::wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(egui_wgpu::wgpu::FragmentState {
+            module: shader,
+            entry_point,
+            targets,
+            // compilation_options: egui_wgpu::wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: egui_wgpu::wgpu::PrimitiveState {
+            topology: egui_wgpu::wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: egui_wgpu::wgpu::FrontFace::Ccw,
+            cull_mode: Some(egui_wgpu::wgpu::Face::Back),
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: egui_wgpu::wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(egui_wgpu::wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: alpha_mode.is_some(),
+            depth_compare: depth_mode.compare(),
+            stencil: egui_wgpu::wgpu::StencilState::default(),
+            bias: egui_wgpu::wgpu::DepthBiasState::default(),
+        }),
+        multisample: egui_wgpu::wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: alpha_mode == Some(transparency::AlphaMode::Cutout)
+                && sample_count > 1,
+        },
+        multiview: None,
+        // cache: None,
+    })
+}
@@ -0,0 +1,41 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Reads the text file at `path` for its lines holding one item each,
+/// trimmed and paired with their line numbers counted from one. Blank lines
+/// and `#` comments are left out. `what` names the file's contents in the
+/// error when it can't be read.
+pub fn read_lines(path: &Path, what: &str) -> Result<Vec<(usize, String)>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {} {}", what, path.display()))?;
+    Ok(text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| (number, line.to_string()))
+        .collect())
+}
+
+/// A path in the temporary directory unique to this test run.
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("{}-{}", std::process::id(), name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_blank_lines_and_comments() {
+        let path = temp_path("lines.txt");
+        std::fs::write(&path, "# Comment\n\n  first item \n\t# Indented\nsecond\n").unwrap();
+        let lines = read_lines(&path, "lines").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            lines,
+            [(3, "first item".to_string()), (5, "second".to_string())]
+        );
+    }
+}
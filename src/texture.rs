@@ -19,19 +19,36 @@ impl Texture {
         Self::from_image(device, queue, &img, Some(label))
     }
 
+    /// Single layer texture array, see `from_layers`.
     pub fn from_image(
         device: &egui_wgpu::wgpu::Device,
         queue: &egui_wgpu::wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
     ) -> Result<Self> {
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        Self::from_layers(device, queue, std::slice::from_ref(img), label)
+    }
+
+    /// Stacks equally sized images into a single texture array, one layer per
+    /// image. The view is created as a `D2Array` so it can be indexed by layer.
+    pub fn from_layers(
+        device: &egui_wgpu::wgpu::Device,
+        queue: &egui_wgpu::wgpu::Queue,
+        images: &[image::DynamicImage],
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let dimensions = match images.first() {
+            Some(img) => img.dimensions(),
+            None => bail!("Texture array needs at least one layer"),
+        };
+        if images.iter().any(|img| img.dimensions() != dimensions) {
+            bail!("All texture array layers must have the same dimensions");
+        }
 
         let size = egui_wgpu::wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
-            depth_or_array_layers: 1,
+            depth_or_array_layers: images.len() as u32,
         };
         let texture = device.create_texture(&egui_wgpu::wgpu::TextureDescriptor {
             label,
@@ -45,23 +62,35 @@ impl Texture {
             view_formats: &[],
         });
 
-        queue.write_texture(
-            egui_wgpu::wgpu::ImageCopyTexture {
-                aspect: egui_wgpu::wgpu::TextureAspect::All,
-                texture: &texture,
-                mip_level: 0,
-                origin: egui_wgpu::wgpu::Origin3d::ZERO,
-            },
-            &rgba,
-            egui_wgpu::wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            size,
-        );
+        for (layer, img) in images.iter().enumerate() {
+            queue.write_texture(
+                egui_wgpu::wgpu::ImageCopyTexture {
+                    aspect: egui_wgpu::wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: egui_wgpu::wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                },
+                &img.to_rgba8(),
+                egui_wgpu::wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * dimensions.0),
+                    rows_per_image: Some(dimensions.1),
+                },
+                egui_wgpu::wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..size
+                },
+            );
+        }
 
-        let view = texture.create_view(&egui_wgpu::wgpu::TextureViewDescriptor::default());
+        let view = texture.create_view(&egui_wgpu::wgpu::TextureViewDescriptor {
+            dimension: Some(egui_wgpu::wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
         let sampler = device.create_sampler(&egui_wgpu::wgpu::SamplerDescriptor {
             address_mode_u: egui_wgpu::wgpu::AddressMode::ClampToEdge,
             address_mode_v: egui_wgpu::wgpu::AddressMode::ClampToEdge,
@@ -85,18 +114,31 @@ impl Texture {
     pub fn create_depth_texture(
         device: &egui_wgpu::wgpu::Device,
         config: &egui_wgpu::wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        Self::create_sized_depth_texture(device, config.width, config.height, sample_count, label)
+    }
+
+    /// Depth texture that doesn't follow the surface size, like a shadow map.
+    /// Its sampler compares against the stored depth.
+    pub fn create_sized_depth_texture(
+        device: &egui_wgpu::wgpu::Device,
+        width: u32,
+        height: u32,
+        sample_count: u32,
         label: &str,
     ) -> Self {
         let size = egui_wgpu::wgpu::Extent3d {
-            width: config.width.max(1),
-            height: config.height.max(1),
+            width: width.max(1),
+            height: height.max(1),
             depth_or_array_layers: 1,
         };
         let desc = egui_wgpu::wgpu::TextureDescriptor {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: egui_wgpu::wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: egui_wgpu::wgpu::TextureUsages::RENDER_ATTACHMENT
@@ -125,4 +167,73 @@ impl Texture {
             sampler,
         }
     }
+
+    /// Single sampled color target that can be read back in a later pass with
+    /// the linear sampler that comes with it.
+    pub fn create_render_target(
+        device: &egui_wgpu::wgpu::Device,
+        width: u32,
+        height: u32,
+        format: egui_wgpu::wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&egui_wgpu::wgpu::TextureDescriptor {
+            label: Some(label),
+            size: egui_wgpu::wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: egui_wgpu::wgpu::TextureDimension::D2,
+            format,
+            usage: egui_wgpu::wgpu::TextureUsages::RENDER_ATTACHMENT
+                | egui_wgpu::wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&egui_wgpu::wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&egui_wgpu::wgpu::SamplerDescriptor {
+            address_mode_u: egui_wgpu::wgpu::AddressMode::ClampToEdge,
+            address_mode_v: egui_wgpu::wgpu::AddressMode::ClampToEdge,
+            address_mode_w: egui_wgpu::wgpu::AddressMode::ClampToEdge,
+            mag_filter: egui_wgpu::wgpu::FilterMode::Linear,
+            min_filter: egui_wgpu::wgpu::FilterMode::Linear,
+            mipmap_filter: egui_wgpu::wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// Multisampled color target the size of the surface. It is only ever
+    /// rendered to and resolved, so it has no sampler of its own.
+    pub fn create_msaa_texture(
+        device: &egui_wgpu::wgpu::Device,
+        config: &egui_wgpu::wgpu::SurfaceConfiguration,
+        format: egui_wgpu::wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+    ) -> egui_wgpu::wgpu::TextureView {
+        let texture = device.create_texture(&egui_wgpu::wgpu::TextureDescriptor {
+            label: Some(label),
+            size: egui_wgpu::wgpu::Extent3d {
+                width: config.width.max(1),
+                height: config.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: egui_wgpu::wgpu::TextureDimension::D2,
+            format,
+            usage: egui_wgpu::wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        texture.create_view(&egui_wgpu::wgpu::TextureViewDescriptor::default())
+    }
 }
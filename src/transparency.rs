@@ -0,0 +1,654 @@
+use crate::{camera, hdr, state, texture};
+use tracing::trace;
+
+const ACCUM_FORMAT: egui_wgpu::wgpu::TextureFormat = egui_wgpu::wgpu::TextureFormat::Rgba16Float;
+const REVEALAGE_FORMAT: egui_wgpu::wgpu::TextureFormat = egui_wgpu::wgpu::TextureFormat::R8Unorm;
+
+/// How the forward path treats the alpha of the textures of opaque instances.
+/// The deferred path keeps a single surface per pixel, so it always draws
+/// them opaque.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Alpha is ignored.
+    Opaque,
+    /// Texels below a cutoff are discarded, and the rest drawn opaque. Uses
+    /// alpha to coverage under MSAA for smoother edges. Shadows are cut out
+    /// the same way.
+    Cutout,
+}
+
+impl AlphaMode {
+    pub const ALL: [AlphaMode; 2] = [AlphaMode::Opaque, AlphaMode::Cutout];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlphaMode::Opaque => "Opaque",
+            AlphaMode::Cutout => "Cutout",
+        }
+    }
+}
+
+/// How transparent instances, the ones with a tint alpha below one, are
+/// blended over the opaque scene. They are drawn after it in both render
+/// paths, depth tested against it without writing depth, and cast no
+/// shadows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Sorted back to front on the CPU and alpha blended, see
+    /// `SortedBlend`. Wrong where geometry intersects.
+    Sorted,
+    /// Weighted blended order-independent transparency, see
+    /// `WeightedBlendedOit`.
+    Weighted,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 2] = [BlendMode::Sorted, BlendMode::Weighted];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlendMode::Sorted => "Sorted",
+            BlendMode::Weighted => "Weighted OIT",
+        }
+    }
+}
+
+/// Sorted alpha blending. Transparent surfaces are blended back to front
+/// into a layer of their own, which is then blended over the opaque scene in
+/// a full screen pass, so the MSAA resolve never touches what was already
+/// drawn into the HDR target.
+pub struct SortedBlend {
+    pipeline: egui_wgpu::wgpu::RenderPipeline,
+    composite_pipeline: egui_wgpu::wgpu::RenderPipeline,
+    composite_layout: egui_wgpu::wgpu::BindGroupLayout,
+    composite_bind_group: egui_wgpu::wgpu::BindGroup,
+    layer: texture::Texture,
+    /// Multisampled target resolved into the layer.
+    msaa_view: Option<egui_wgpu::wgpu::TextureView>,
+    sample_count: u32,
+    depth_mode: camera::DepthMode,
+}
+
+impl SortedBlend {
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        config: &egui_wgpu::wgpu::SurfaceConfiguration,
+        scene_layout: &egui_wgpu::wgpu::PipelineLayout,
+        scene_shader: &egui_wgpu::wgpu::ShaderModule,
+        sample_count: u32,
+        depth_mode: camera::DepthMode,
+    ) -> Self {
+        let composite_layout =
+            device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+                entries: &[egui_wgpu::wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+                    ty: egui_wgpu::wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: egui_wgpu::wgpu::TextureViewDimension::D2,
+                        sample_type: egui_wgpu::wgpu::TextureSampleType::Float {
+                            filterable: false,
+                        },
+                    },
+                    count: None,
+                }],
+                label: Some("sorted_bind_group_layout"),
+            });
+
+        let shader = device.create_shader_module(egui_wgpu::wgpu::include_wgsl!("sorted.wgsl"));
+        let pipeline_layout =
+            device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+                label: Some("Sorted Composite Pipeline Layout"),
+                bind_group_layouts: &[&composite_layout],
+                push_constant_ranges: &[],
+            });
+        let composite_pipeline =
+            device.create_render_pipeline(&egui_wgpu::wgpu::RenderPipelineDescriptor {
+                label: Some("Sorted Composite Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: egui_wgpu::wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(egui_wgpu::wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(egui_wgpu::wgpu::ColorTargetState {
+                        format: hdr::HDR_FORMAT,
+                        blend: Some(egui_wgpu::wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: egui_wgpu::wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: egui_wgpu::wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        let layer = create_layer(device, config);
+        let composite_bind_group = create_layer_bind_group(device, &composite_layout, &layer);
+        Self {
+            pipeline: create_sorted_pipeline(
+                device,
+                scene_layout,
+                scene_shader,
+                sample_count,
+                depth_mode,
+            ),
+            composite_pipeline,
+            composite_layout,
+            composite_bind_group,
+            layer,
+            msaa_view: create_layer_msaa_view(device, config, sample_count),
+            sample_count,
+            depth_mode,
+        }
+    }
+
+    /// Recreates the layer, and the blend pipeline if the sample count
+    /// changed.
+    pub fn resize(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        config: &egui_wgpu::wgpu::SurfaceConfiguration,
+        scene_layout: &egui_wgpu::wgpu::PipelineLayout,
+        scene_shader: &egui_wgpu::wgpu::ShaderModule,
+        sample_count: u32,
+    ) {
+        self.layer = create_layer(device, config);
+        self.composite_bind_group =
+            create_layer_bind_group(device, &self.composite_layout, &self.layer);
+        self.msaa_view = create_layer_msaa_view(device, config, sample_count);
+        if sample_count != self.sample_count {
+            self.sample_count = sample_count;
+            self.pipeline = create_sorted_pipeline(
+                device,
+                scene_layout,
+                scene_shader,
+                sample_count,
+                self.depth_mode,
+            );
+            trace!("Sorted blend pipeline recreated for {}x MSAA", sample_count);
+        }
+    }
+
+    /// Recreates the blend pipeline for testing against `depth_mode`.
+    pub fn set_depth_mode(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        scene_layout: &egui_wgpu::wgpu::PipelineLayout,
+        scene_shader: &egui_wgpu::wgpu::ShaderModule,
+        depth_mode: camera::DepthMode,
+    ) {
+        self.depth_mode = depth_mode;
+        self.pipeline = create_sorted_pipeline(
+            device,
+            scene_layout,
+            scene_shader,
+            self.sample_count,
+            depth_mode,
+        );
+    }
+
+    /// Starts the pass blending sorted transparent surfaces into the cleared
+    /// layer, tested against the opaque scene's `depth`, with its pipeline
+    /// set. The scene's bind groups still need to be set before drawing.
+    pub fn begin_pass<'a>(
+        &'a self,
+        encoder: &'a mut egui_wgpu::wgpu::CommandEncoder,
+        depth: &'a egui_wgpu::wgpu::TextureView,
+    ) -> egui_wgpu::wgpu::RenderPass<'a> {
+        let mut render_pass = encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
+            label: Some("Sorted Transparent Pass"),
+            color_attachments: &[Some(egui_wgpu::wgpu::RenderPassColorAttachment {
+                view: self.msaa_view.as_ref().unwrap_or(&self.layer.view),
+                resolve_target: self.msaa_view.as_ref().map(|_| &self.layer.view),
+                ops: egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Clear(egui_wgpu::wgpu::Color::TRANSPARENT),
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(egui_wgpu::wgpu::RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Load,
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass
+    }
+
+    /// Blends the layer over `output`.
+    pub fn composite(
+        &self,
+        encoder: &mut egui_wgpu::wgpu::CommandEncoder,
+        output: &egui_wgpu::wgpu::TextureView,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
+            label: Some("Sorted Composite Pass"),
+            color_attachments: &[Some(egui_wgpu::wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Load,
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &self.composite_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// Weighted blended order-independent transparency (McGuire and Bavoil
+/// 2013). Transparent surfaces are accumulated in any order into a weighted
+/// color sum and a revealage product, which are then averaged and blended
+/// over the opaque scene in a full screen pass.
+pub struct WeightedBlendedOit {
+    accumulate_pipeline: egui_wgpu::wgpu::RenderPipeline,
+    composite_pipeline: egui_wgpu::wgpu::RenderPipeline,
+    composite_layout: egui_wgpu::wgpu::BindGroupLayout,
+    composite_bind_group: egui_wgpu::wgpu::BindGroup,
+    accum: texture::Texture,
+    revealage: texture::Texture,
+    /// Multisampled accumulation targets resolved into the ones above.
+    msaa_views: Option<(egui_wgpu::wgpu::TextureView, egui_wgpu::wgpu::TextureView)>,
+    sample_count: u32,
+    depth_mode: camera::DepthMode,
+}
+
+impl WeightedBlendedOit {
+    pub fn new(
+        device: &egui_wgpu::wgpu::Device,
+        config: &egui_wgpu::wgpu::SurfaceConfiguration,
+        scene_layout: &egui_wgpu::wgpu::PipelineLayout,
+        scene_shader: &egui_wgpu::wgpu::ShaderModule,
+        sample_count: u32,
+        depth_mode: camera::DepthMode,
+    ) -> Self {
+        let composite_entry = |binding| egui_wgpu::wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: egui_wgpu::wgpu::ShaderStages::FRAGMENT,
+            ty: egui_wgpu::wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: egui_wgpu::wgpu::TextureViewDimension::D2,
+                sample_type: egui_wgpu::wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        };
+        let composite_layout =
+            device.create_bind_group_layout(&egui_wgpu::wgpu::BindGroupLayoutDescriptor {
+                entries: &[composite_entry(0), composite_entry(1)],
+                label: Some("oit_bind_group_layout"),
+            });
+
+        let shader = device.create_shader_module(egui_wgpu::wgpu::include_wgsl!("oit.wgsl"));
+        let pipeline_layout =
+            device.create_pipeline_layout(&egui_wgpu::wgpu::PipelineLayoutDescriptor {
+                label: Some("OIT Composite Pipeline Layout"),
+                bind_group_layouts: &[&composite_layout],
+                push_constant_ranges: &[],
+            });
+        let composite_pipeline =
+            device.create_render_pipeline(&egui_wgpu::wgpu::RenderPipelineDescriptor {
+                label: Some("OIT Composite Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: egui_wgpu::wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(egui_wgpu::wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(egui_wgpu::wgpu::ColorTargetState {
+                        format: hdr::HDR_FORMAT,
+                        blend: Some(egui_wgpu::wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: egui_wgpu::wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: egui_wgpu::wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        let (accum, revealage) = create_targets(device, config);
+        let composite_bind_group = create_bind_group(device, &composite_layout, &accum, &revealage);
+        Self {
+            accumulate_pipeline: create_accumulate_pipeline(
+                device,
+                scene_layout,
+                scene_shader,
+                sample_count,
+                depth_mode,
+            ),
+            composite_pipeline,
+            composite_layout,
+            composite_bind_group,
+            accum,
+            revealage,
+            msaa_views: create_msaa_views(device, config, sample_count),
+            sample_count,
+            depth_mode,
+        }
+    }
+
+    /// Recreates the targets, and the accumulation pipeline if the sample
+    /// count changed.
+    pub fn resize(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        config: &egui_wgpu::wgpu::SurfaceConfiguration,
+        scene_layout: &egui_wgpu::wgpu::PipelineLayout,
+        scene_shader: &egui_wgpu::wgpu::ShaderModule,
+        sample_count: u32,
+    ) {
+        (self.accum, self.revealage) = create_targets(device, config);
+        self.composite_bind_group =
+            create_bind_group(device, &self.composite_layout, &self.accum, &self.revealage);
+        self.msaa_views = create_msaa_views(device, config, sample_count);
+        if sample_count != self.sample_count {
+            self.sample_count = sample_count;
+            self.accumulate_pipeline = create_accumulate_pipeline(
+                device,
+                scene_layout,
+                scene_shader,
+                sample_count,
+                self.depth_mode,
+            );
+            trace!("OIT pipeline recreated for {}x MSAA", sample_count);
+        }
+    }
+
+    /// Recreates the accumulation pipeline for testing against `depth_mode`.
+    pub fn set_depth_mode(
+        &mut self,
+        device: &egui_wgpu::wgpu::Device,
+        scene_layout: &egui_wgpu::wgpu::PipelineLayout,
+        scene_shader: &egui_wgpu::wgpu::ShaderModule,
+        depth_mode: camera::DepthMode,
+    ) {
+        self.depth_mode = depth_mode;
+        self.accumulate_pipeline = create_accumulate_pipeline(
+            device,
+            scene_layout,
+            scene_shader,
+            self.sample_count,
+            depth_mode,
+        );
+    }
+
+    /// Starts the pass accumulating transparent surfaces, tested against the
+    /// opaque scene's `depth`, with its pipeline set. The scene's bind groups
+    /// still need to be set before drawing.
+    pub fn begin_accumulate_pass<'a>(
+        &'a self,
+        encoder: &'a mut egui_wgpu::wgpu::CommandEncoder,
+        depth: &'a egui_wgpu::wgpu::TextureView,
+    ) -> egui_wgpu::wgpu::RenderPass<'a> {
+        let target = |texture: &'a texture::Texture,
+                      msaa_view: Option<&'a egui_wgpu::wgpu::TextureView>,
+                      clear| {
+            Some(egui_wgpu::wgpu::RenderPassColorAttachment {
+                view: msaa_view.unwrap_or(&texture.view),
+                resolve_target: msaa_view.map(|_| &texture.view),
+                ops: egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Clear(clear),
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                },
+            })
+        };
+        let mut render_pass = encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
+            label: Some("OIT Accumulate Pass"),
+            color_attachments: &[
+                target(
+                    &self.accum,
+                    self.msaa_views.as_ref().map(|(accum, _)| accum),
+                    egui_wgpu::wgpu::Color::TRANSPARENT,
+                ),
+                target(
+                    &self.revealage,
+                    self.msaa_views.as_ref().map(|(_, revealage)| revealage),
+                    egui_wgpu::wgpu::Color::WHITE,
+                ),
+            ],
+            depth_stencil_attachment: Some(egui_wgpu::wgpu::RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Load,
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.accumulate_pipeline);
+        render_pass
+    }
+
+    /// Blends the accumulated surfaces over `output`.
+    pub fn composite(
+        &self,
+        encoder: &mut egui_wgpu::wgpu::CommandEncoder,
+        output: &egui_wgpu::wgpu::TextureView,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&egui_wgpu::wgpu::RenderPassDescriptor {
+            label: Some("OIT Composite Pass"),
+            color_attachments: &[Some(egui_wgpu::wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: egui_wgpu::wgpu::Operations {
+                    load: egui_wgpu::wgpu::LoadOp::Load,
+                    store: egui_wgpu::wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &self.composite_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_accumulate_pipeline(
+    device: &egui_wgpu::wgpu::Device,
+    scene_layout: &egui_wgpu::wgpu::PipelineLayout,
+    scene_shader: &egui_wgpu::wgpu::ShaderModule,
+    sample_count: u32,
+    depth_mode: camera::DepthMode,
+) -> egui_wgpu::wgpu::RenderPipeline {
+    state::create_render_pipeline(
+        device,
+        scene_layout,
+        scene_shader,
+        "fs_accumulate",
+        &[
+            Some(egui_wgpu::wgpu::ColorTargetState {
+                format: ACCUM_FORMAT,
+                blend: Some(egui_wgpu::wgpu::BlendState {
+                    color: egui_wgpu::wgpu::BlendComponent {
+                        src_factor: egui_wgpu::wgpu::BlendFactor::One,
+                        dst_factor: egui_wgpu::wgpu::BlendFactor::One,
+                        operation: egui_wgpu::wgpu::BlendOperation::Add,
+                    },
+                    alpha: egui_wgpu::wgpu::BlendComponent {
+                        src_factor: egui_wgpu::wgpu::BlendFactor::One,
+                        dst_factor: egui_wgpu::wgpu::BlendFactor::One,
+                        operation: egui_wgpu::wgpu::BlendOperation::Add,
+                    },
+                }),
+                write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
+            }),
+            // Multiplies the revealage by one minus the surface's alpha
+            Some(egui_wgpu::wgpu::ColorTargetState {
+                format: REVEALAGE_FORMAT,
+                blend: Some(egui_wgpu::wgpu::BlendState {
+                    color: egui_wgpu::wgpu::BlendComponent {
+                        src_factor: egui_wgpu::wgpu::BlendFactor::Zero,
+                        dst_factor: egui_wgpu::wgpu::BlendFactor::OneMinusSrc,
+                        operation: egui_wgpu::wgpu::BlendOperation::Add,
+                    },
+                    alpha: egui_wgpu::wgpu::BlendComponent::REPLACE,
+                }),
+                write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
+            }),
+        ],
+        None,
+        sample_count,
+        depth_mode,
+    )
+}
+
+fn create_targets(
+    device: &egui_wgpu::wgpu::Device,
+    config: &egui_wgpu::wgpu::SurfaceConfiguration,
+) -> (texture::Texture, texture::Texture) {
+    let target = |format, label| {
+        texture::Texture::create_render_target(device, config.width, config.height, format, label)
+    };
+    (
+        target(ACCUM_FORMAT, "oit_accum"),
+        target(REVEALAGE_FORMAT, "oit_revealage"),
+    )
+}
+
+fn create_msaa_views(
+    device: &egui_wgpu::wgpu::Device,
+    config: &egui_wgpu::wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> Option<(egui_wgpu::wgpu::TextureView, egui_wgpu::wgpu::TextureView)> {
+    (sample_count > 1).then(|| {
+        (
+            texture::Texture::create_msaa_texture(
+                device,
+                config,
+                ACCUM_FORMAT,
+                sample_count,
+                "oit_accum_msaa",
+            ),
+            texture::Texture::create_msaa_texture(
+                device,
+                config,
+                REVEALAGE_FORMAT,
+                sample_count,
+                "oit_revealage_msaa",
+            ),
+        )
+    })
+}
+
+fn create_bind_group(
+    device: &egui_wgpu::wgpu::Device,
+    layout: &egui_wgpu::wgpu::BindGroupLayout,
+    accum: &texture::Texture,
+    revealage: &texture::Texture,
+) -> egui_wgpu::wgpu::BindGroup {
+    device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            egui_wgpu::wgpu::BindGroupEntry {
+                binding: 0,
+                resource: egui_wgpu::wgpu::BindingResource::TextureView(&accum.view),
+            },
+            egui_wgpu::wgpu::BindGroupEntry {
+                binding: 1,
+                resource: egui_wgpu::wgpu::BindingResource::TextureView(&revealage.view),
+            },
+        ],
+        label: Some("oit_bind_group"),
+    })
+}
+
+/// Blends straight alpha colors back to front, leaving premultiplied color
+/// and the combined coverage in the layer.
+fn create_sorted_pipeline(
+    device: &egui_wgpu::wgpu::Device,
+    scene_layout: &egui_wgpu::wgpu::PipelineLayout,
+    scene_shader: &egui_wgpu::wgpu::ShaderModule,
+    sample_count: u32,
+    depth_mode: camera::DepthMode,
+) -> egui_wgpu::wgpu::RenderPipeline {
+    state::create_render_pipeline(
+        device,
+        scene_layout,
+        scene_shader,
+        "fs_main",
+        &[Some(egui_wgpu::wgpu::ColorTargetState {
+            format: hdr::HDR_FORMAT,
+            blend: Some(egui_wgpu::wgpu::BlendState {
+                color: egui_wgpu::wgpu::BlendComponent {
+                    src_factor: egui_wgpu::wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: egui_wgpu::wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: egui_wgpu::wgpu::BlendOperation::Add,
+                },
+                alpha: egui_wgpu::wgpu::BlendComponent {
+                    src_factor: egui_wgpu::wgpu::BlendFactor::One,
+                    dst_factor: egui_wgpu::wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: egui_wgpu::wgpu::BlendOperation::Add,
+                },
+            }),
+            write_mask: egui_wgpu::wgpu::ColorWrites::ALL,
+        })],
+        None,
+        sample_count,
+        depth_mode,
+    )
+}
+
+fn create_layer(
+    device: &egui_wgpu::wgpu::Device,
+    config: &egui_wgpu::wgpu::SurfaceConfiguration,
+) -> texture::Texture {
+    texture::Texture::create_render_target(
+        device,
+        config.width,
+        config.height,
+        hdr::HDR_FORMAT,
+        "sorted_layer",
+    )
+}
+
+fn create_layer_msaa_view(
+    device: &egui_wgpu::wgpu::Device,
+    config: &egui_wgpu::wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> Option<egui_wgpu::wgpu::TextureView> {
+    (sample_count > 1).then(|| {
+        texture::Texture::create_msaa_texture(
+            device,
+            config,
+            hdr::HDR_FORMAT,
+            sample_count,
+            "sorted_layer_msaa",
+        )
+    })
+}
+
+fn create_layer_bind_group(
+    device: &egui_wgpu::wgpu::Device,
+    layout: &egui_wgpu::wgpu::BindGroupLayout,
+    layer: &texture::Texture,
+) -> egui_wgpu::wgpu::BindGroup {
+    device.create_bind_group(&egui_wgpu::wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[egui_wgpu::wgpu::BindGroupEntry {
+            binding: 0,
+            resource: egui_wgpu::wgpu::BindingResource::TextureView(&layer.view),
+        }],
+        label: Some("sorted_bind_group"),
+    })
+}